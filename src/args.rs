@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 pub struct Args {
@@ -10,4 +10,35 @@ pub struct Args {
 
     #[clap(short, long, help = "Path to log file (if the file exists, it will be overwritten)")]
     pub log_file: Option<String>,
+
+    #[clap(long, help = "Write the touchpad's multitouch events to a trace file")]
+    pub record: Option<String>,
+
+    #[clap(long, conflicts_with = "record", help = "Feed the events of a trace file to the gestures engine instead of reading the touchpad, then exit")]
+    pub replay: Option<String>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[clap(about = "Send a command to the running instance: status, reload, pause, resume, list-gestures, active-window, trigger <name>, subscribe")]
+    Ctl {
+        #[clap(required = true, help = "Command and its arguments")]
+        command: Vec<String>,
+    },
+    #[clap(about = "Print performed gestures as YAML for the gestures list, until interrupted")]
+    Record {
+        #[clap(short, long, help = "Name of the recorded gestures, numbered if more than one is performed")]
+        name: Option<String>,
+
+        #[clap(short, long, help = "Config file to append the recorded gestures to, 'gestures:' must be its last top-level key")]
+        append: Option<String>,
+    },
+    #[clap(about = "Run simulation scripts against the config and check which gestures they match, without a touchpad")]
+    Simulate {
+        #[clap(required = true, help = "Script files")]
+        scripts: Vec<String>,
+    },
 }
@@ -1,8 +1,14 @@
 use std::{collections::HashMap, path::PathBuf};
 use std::path::Path;
+use std::time::Duration;
 use regex::Regex;
 use bitflags::bitflags;
+use crate::dbus::DBusCall;
+use crate::executor::GestureAction;
+use crate::keys::KeyChord;
 use crate::sequence_step::{DefinedSequenceStep, DefinedSequenceStepRaw};
+use crate::window_monitor::WindowMonitorKind;
+use crate::Window;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Direction {
@@ -10,9 +16,151 @@ pub enum Direction {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
     None,
 }
 
+impl Direction {
+    pub fn is_diagonal(&self) -> bool {
+        matches!(self, Direction::UpLeft | Direction::UpRight | Direction::DownLeft | Direction::DownRight)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::UpLeft => "up-left",
+            Direction::UpRight => "up-right",
+            Direction::DownLeft => "down-left",
+            Direction::DownRight => "down-right",
+            Direction::None => "",
+        }
+    }
+
+    /// Parses directions like `left` or `up-left` (also `up left` and `up_left`)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.replace([' ', '_'], "-").as_str() {
+            "up" => Some(Direction::Up),
+            "down" => Some(Direction::Down),
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            "up-left" => Some(Direction::UpLeft),
+            "up-right" => Some(Direction::UpRight),
+            "down-left" => Some(Direction::DownLeft),
+            "down-right" => Some(Direction::DownRight),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PinchDirection {
+    In,
+    Out,
+}
+
+impl PinchDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PinchDirection::In => "in",
+            PinchDirection::Out => "out",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RotationDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl RotationDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RotationDirection::Clockwise => "cw",
+            RotationDirection::CounterClockwise => "ccw",
+        }
+    }
+}
+
+/// Parses durations like `500ms`, `1.5s` or a plain number of milliseconds
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, multiplier) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else {
+        (s, 0.001)
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(n) if n >= 0.0 => Ok(Duration::from_secs_f64(n * multiplier)),
+        _ => Err(format!("Invalid duration: \"{}\"", s)),
+    }
+}
+
+pub fn parse_duration_value(value: &serde_yaml::Value) -> Result<Duration, String> {
+    match value {
+        serde_yaml::Value::String(s) => parse_duration(s),
+        serde_yaml::Value::Number(n) => n.as_u64().map(Duration::from_millis).ok_or_else(|| format!("Invalid duration: {}", n)),
+        v => Err(format!("Invalid duration: {:?}", v)),
+    }
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: serde_yaml::Value = serde::Deserialize::deserialize(deserializer)?;
+    parse_duration_value(&value).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<serde_yaml::Value> = serde::Deserialize::deserialize(deserializer)?;
+    value.map(|v| parse_duration_value(&v)).transpose().map_err(serde::de::Error::custom)
+}
+
+/// Reads a list of program arguments, numbers and booleans are taken as written so they don't need quoting
+fn deserialize_optional_args<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let values: Option<Vec<serde_yaml::Value>> = serde::Deserialize::deserialize(deserializer)?;
+    values.map(|values| values.into_iter().map(|value| match value {
+        serde_yaml::Value::String(s) => Ok(s),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        v => Err(serde::de::Error::custom(format!("Invalid argument: {:?}", v))),
+    }).collect()).transpose()
+}
+
+/// Accepts a single window monitor or a list of window monitors to probe in order
+fn deserialize_window_monitors<'de, D>(deserializer: D) -> Result<Vec<WindowMonitorKind>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(WindowMonitorKind),
+        Many(Vec<WindowMonitorKind>),
+    }
+
+    Ok(match serde::Deserialize::deserialize(deserializer)? {
+        OneOrMany::One(kind) => vec![kind],
+        OneOrMany::Many(kinds) => kinds,
+    })
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, serde::Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Edge {
@@ -22,12 +170,24 @@ pub enum Edge {
     Right,
 }
 
+impl Edge {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Edge::Top => "top",
+            Edge::Bottom => "bottom",
+            Edge::Left => "left",
+            Edge::Right => "right",
+        }
+    }
+}
+
 bitflags! {
     #[derive(Debug, Clone, PartialEq, Default)]
     pub struct RepeatMode: u8 {
-        const None = 0b00;
-        const Tap = 0b01;
-        const Slide = 0b10;
+        const None = 0b000;
+        const Tap = 0b001;
+        const Slide = 0b010;
+        const Continuous = 0b100;
     }
 }
 
@@ -42,6 +202,7 @@ impl<'de> serde::Deserialize<'de> for RepeatMode {
             match mode.to_lowercase().as_str() {
                 "tap" => { repeat_mode.insert(RepeatMode::Tap); },
                 "slide" => { repeat_mode.insert(RepeatMode::Slide); },
+                "continuous" => { repeat_mode.insert(RepeatMode::Continuous); },
                 _ => return Err(serde::de::Error::custom(format!("Invalid repeat mode: {}", mode))),
             }
         }
@@ -49,13 +210,65 @@ impl<'de> serde::Deserialize<'de> for RepeatMode {
     }
 }
 
+/// State the active window must be in for a gesture to be active, unset fields match any state
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowCondition {
+    pub fullscreen: Option<bool>,
+    pub maximized: Option<bool>,
+    pub minimized: Option<bool>,
+    pub floating: Option<bool>,
+    pub workspace: Option<String>,
+}
+
+impl WindowCondition {
+    pub fn matches(&self, window: &Window) -> bool {
+        self.fullscreen.is_none_or(|fullscreen| fullscreen == window.fullscreen)
+            && self.maximized.is_none_or(|maximized| maximized == window.maximized)
+            && self.minimized.is_none_or(|minimized| minimized == window.minimized)
+            && self.floating.is_none_or(|floating| floating == window.floating)
+            && self.workspace.as_ref().is_none_or(|workspace| Some(workspace) == window.workspace.as_ref())
+    }
+}
+
+/// What a gesture does when it is recognized
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Command,
+    Exec,
+    Key,
+    Dbus,
+}
+
+impl Action {
+    /// Name of the gesture field defining the action
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            Action::Command => "command",
+            Action::Exec => "exec",
+            Action::Key => "keys",
+            Action::Dbus => "dbus",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Gesture {
     pub name: String,
     pub sequence: Vec<DefinedSequenceStep>,
     pub edge: Option<Edge>,
     pub repeat_mode: RepeatMode,
-    pub command: String,
+    /// maximum time between the end of a step and the start of the next one
+    pub timeout: Option<Duration>,
+    /// state of the active window the gesture is restricted to
+    pub when: WindowCondition,
+    /// none if a continuous gesture only has phase commands
+    pub action: Option<GestureAction>,
+    /// commands for the phases of a continuous gesture, `action` is used for phases without one
+    pub begin_command: Option<String>,
+    pub update_command: Option<String>,
+    pub end_command: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -65,14 +278,76 @@ pub struct GestureRaw {
     pub edge: Option<Edge>,
     #[serde(default)]
     pub repeat_mode: RepeatMode,
-    pub command: String,
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub timeout: Option<Duration>,
+    #[serde(default)]
+    pub when: WindowCondition,
+    pub command: Option<String>,
+    pub begin_command: Option<String>,
+    pub update_command: Option<String>,
+    pub end_command: Option<String>,
+    pub action: Option<Action>,
+    /// program and arguments, run without a shell
+    #[serde(default, deserialize_with = "deserialize_optional_args")]
+    pub exec: Option<Vec<String>>,
+    pub keys: Option<KeyChord>,
+    pub dbus: Option<DBusCall>,
 }
 
 impl Gesture {
-    pub fn from_raw(raw: GestureRaw, distances: &HashMap<String, f32>) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn from_raw(raw: GestureRaw, options: &Options) -> Result<Self, Box<dyn std::error::Error>> {
         let mut sequence = Vec::new();
         for step_raw in &raw.sequence {
-            sequence.push(DefinedSequenceStep::from_raw(step_raw.clone(), distances)?);
+            sequence.push(DefinedSequenceStep::from_raw(step_raw.clone(), options)?);
+        }
+
+        let has_phase_commands = raw.begin_command.is_some() || raw.update_command.is_some() || raw.end_command.is_some();
+        let defined_actions = [
+            raw.command.is_some().then_some(Action::Command),
+            raw.exec.is_some().then_some(Action::Exec),
+            raw.keys.is_some().then_some(Action::Key),
+            raw.dbus.is_some().then_some(Action::Dbus),
+        ].into_iter().flatten().collect::<Vec<_>>();
+        if defined_actions.len() > 1 {
+            return Err(format!("Gesture '{}' has more than one of command, exec, keys and dbus", raw.name).into());
+        }
+        let action_kind = raw.action.or(defined_actions.first().copied()).unwrap_or(Action::Command);
+        if let Some(defined) = defined_actions.first()
+            && *defined != action_kind
+        {
+            return Err(format!("Gesture '{}' has {} which doesn't match its action", raw.name, defined.field_name()).into());
+        }
+
+        let action = match action_kind {
+            Action::Command => raw.command.map(GestureAction::Command),
+            Action::Exec => {
+                let exec = raw.exec.ok_or_else(|| format!("Gesture '{}' has exec action but no exec", raw.name))?;
+                if exec.is_empty() {
+                    return Err(format!("Gesture '{}' has an empty exec", raw.name).into());
+                }
+                Some(GestureAction::Exec(exec))
+            }
+            Action::Key => {
+                let keys = raw.keys.ok_or_else(|| format!("Gesture '{}' has key action but no keys", raw.name))?;
+                if has_phase_commands {
+                    return Err(format!("Gesture '{}' has both keys and phase commands", raw.name).into());
+                }
+                if raw.repeat_mode.contains(RepeatMode::Continuous) {
+                    return Err(format!("Gesture '{}' has key action which can't be continuous", raw.name).into());
+                }
+                Some(GestureAction::Keys(keys))
+            }
+            Action::Dbus => {
+                let call = raw.dbus.ok_or_else(|| format!("Gesture '{}' has dbus action but no dbus call", raw.name))?;
+                call.validate().map_err(|e| format!("Gesture '{}' has an invalid dbus call: {}", raw.name, e))?;
+                Some(GestureAction::DBus(call))
+            }
+        };
+        if has_phase_commands && !raw.repeat_mode.contains(RepeatMode::Continuous) {
+            return Err(format!("Gesture '{}' has phase commands but its repeat mode is not continuous", raw.name).into());
+        }
+        if action.is_none() && !has_phase_commands {
+            return Err(format!("Gesture '{}' has no command", raw.name).into());
         }
 
         Ok(Gesture {
@@ -80,12 +355,37 @@ impl Gesture {
             sequence,
             edge: raw.edge,
             repeat_mode: raw.repeat_mode,
-            command: raw.command,
+            timeout: raw.timeout,
+            when: raw.when,
+            action,
+            begin_command: raw.begin_command,
+            update_command: raw.update_command,
+            end_command: raw.end_command,
         })
     }
+
+    /// Whether this gesture is `other` with moves replaced by flicks in the same direction with the same fingers,
+    /// assuming both matched the same sequence
+    pub fn is_flick_of(&self, other: &Gesture) -> bool {
+        if self.sequence.len() != other.sequence.len() {
+            return false;
+        }
+
+        let mut replaces_move = false;
+        for (step, other_step) in self.sequence.iter().zip(&other.sequence) {
+            match (step, other_step) {
+                (DefinedSequenceStep::Flick { fingers, direction, .. }, DefinedSequenceStep::Move { fingers: f, direction: d, .. }) if fingers == f && direction == d => {
+                    replaces_move = true;
+                }
+                (DefinedSequenceStep::Flick { .. }, DefinedSequenceStep::Move { .. }) | (DefinedSequenceStep::Move { .. }, DefinedSequenceStep::Flick { .. }) => return false,
+                _ => {}
+            }
+        }
+        replaces_move
+    }
 }
 
-#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct EdgeOptions {
     #[serde(default = "EdgeOptions::default_threshold")]
     pub threshold: f32,
@@ -99,7 +399,17 @@ impl EdgeOptions {
     fn default_sensitivity() -> f32 { 0.5 }
 }
 
-#[derive(Debug, Default, Clone, serde::Deserialize)]
+/// Same values as the serde defaults, for configs without an `edge:` section
+impl Default for EdgeOptions {
+    fn default() -> Self {
+        EdgeOptions {
+            threshold: Self::default_threshold(),
+            sensitivity: Self::default_sensitivity(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct Options {
     #[serde(default = "Options::default_move_threshold")]
     pub move_threshold: f32,
@@ -109,10 +419,51 @@ pub struct Options {
     pub run_all_matches: bool,
     #[serde(default)]
     pub distance: HashMap<String, f32>,
+    #[serde(default = "Options::default_pinch_threshold")]
+    pub pinch_threshold: f32,
+    #[serde(default = "Options::default_rotation_threshold")]
+    pub rotation_threshold: f32,
+    #[serde(default = "Options::default_tap_interval", deserialize_with = "deserialize_duration")]
+    pub tap_interval: Duration,
+    #[serde(default = "Options::default_flick_velocity")]
+    pub flick_velocity: f32,
+    #[serde(default = "Options::default_continuous_update_threshold")]
+    pub continuous_update_threshold: f32,
+    /// interfaces probed in order to track the active window, all of them if empty
+    #[serde(default, deserialize_with = "deserialize_window_monitors")]
+    pub window_monitor: Vec<WindowMonitorKind>,
 }
 
 impl Options {
     fn default_move_threshold() -> f32 { 0.15 }
+
+    fn default_pinch_threshold() -> f32 { 0.25 }
+
+    fn default_rotation_threshold() -> f32 { 20.0 }
+
+    fn default_tap_interval() -> Duration { Duration::from_millis(300) }
+
+    fn default_flick_velocity() -> f32 { 3.5 }
+
+    fn default_continuous_update_threshold() -> f32 { 0.05 }
+}
+
+// Not derived, so that configs without an `options:` section get the same values as fields missing from one
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            move_threshold: Self::default_move_threshold(),
+            edge: EdgeOptions::default(),
+            run_all_matches: false,
+            distance: HashMap::new(),
+            pinch_threshold: Self::default_pinch_threshold(),
+            rotation_threshold: Self::default_rotation_threshold(),
+            tap_interval: Self::default_tap_interval(),
+            flick_velocity: Self::default_flick_velocity(),
+            continuous_update_threshold: Self::default_continuous_update_threshold(),
+            window_monitor: Vec::new(),
+        }
+    }
 }
 
 type ApplicationGesturesRaw = HashMap<String, Vec<GestureRaw>>;
@@ -135,12 +486,15 @@ pub struct ConfigRaw {
     pub application_gestures: Option<ApplicationGesturesRaw>,
 }
 
+/// Gestures and options, parsed from a config file and its imports
 #[derive(Debug)]
 pub struct Config {
     pub import: Vec<PathBuf>,
     pub options: Options,
     pub gestures: Vec<Gesture>,
     pub application_gestures: ApplicationGestures,
+    /// whether any gesture uses a diagonal move, in which case moves are classified into 8 sectors instead of 4
+    pub diagonal_moves: bool,
 }
 
 // TODO: clean this up
@@ -149,7 +503,7 @@ impl Config {
         let mut gestures = if let Some(raw_gestures) = &config_raw.gestures {
             let mut gs = Vec::new();
             for raw_gesture in raw_gestures {
-                gs.push(Gesture::from_raw(raw_gesture.clone(), &options.distance)?);
+                gs.push(Gesture::from_raw(raw_gesture.clone(), options)?);
             }
             gs
         } else {
@@ -188,7 +542,7 @@ impl Config {
             for (app_name, raw_gestures) in application_gestures_raw {
                 let mut gestures = Vec::new();
                 for raw_gesture in &raw_gestures {
-                    gestures.push(Gesture::from_raw(raw_gesture.clone(), &options.distance)?);
+                    gestures.push(Gesture::from_raw(raw_gesture.clone(), options)?);
                 }
 
                 if let Some((first, second)) = app_name.split_once(',') {
@@ -230,6 +584,7 @@ impl Config {
             options: options.clone(),
             gestures,
             application_gestures,
+            diagonal_moves: false,
         })
     }
 }
@@ -237,6 +592,8 @@ impl Config {
 fn are_gestures_conflicting(g1: &Gesture, g2: &Gesture) -> bool {
     if g1.sequence.len() != g2.sequence.len()
         || g1.edge != g2.edge
+        || g1.timeout != g2.timeout
+        || g1.when != g2.when
     {
         return false;
     }
@@ -249,8 +606,28 @@ fn are_gestures_conflicting(g1: &Gesture, g2: &Gesture) -> bool {
                     return false;
                 }
             }
-            (DefinedSequenceStep::Move { fingers: f1, direction: d1, distance: dst1 }, DefinedSequenceStep::Move { fingers: f2, direction: d2, distance: dst2 }) => {
-                if f1 != f2 || d1 != d2 || dst1 != dst2 {
+            (DefinedSequenceStep::Move { fingers: f1, direction: d1, distance: dst1, max_duration: m1 }, DefinedSequenceStep::Move { fingers: f2, direction: d2, distance: dst2, max_duration: m2 }) => {
+                if f1 != f2 || d1 != d2 || dst1 != dst2 || m1 != m2 {
+                    return false;
+                }
+            }
+            (DefinedSequenceStep::Flick { fingers: f1, direction: d1, distance: dst1, velocity: v1, max_duration: m1 }, DefinedSequenceStep::Flick { fingers: f2, direction: d2, distance: dst2, velocity: v2, max_duration: m2 }) => {
+                if f1 != f2 || d1 != d2 || dst1 != dst2 || v1 != v2 || m1 != m2 {
+                    return false;
+                }
+            }
+            (DefinedSequenceStep::Pinch { fingers: f1, direction: d1, scale: s1, max_duration: m1 }, DefinedSequenceStep::Pinch { fingers: f2, direction: d2, scale: s2, max_duration: m2 }) => {
+                if f1 != f2 || d1 != d2 || s1 != s2 || m1 != m2 {
+                    return false;
+                }
+            }
+            (DefinedSequenceStep::Rotate { fingers: f1, direction: d1, angle: a1, max_duration: m1 }, DefinedSequenceStep::Rotate { fingers: f2, direction: d2, angle: a2, max_duration: m2 }) => {
+                if f1 != f2 || d1 != d2 || a1 != a2 || m1 != m2 {
+                    return false;
+                }
+            }
+            (DefinedSequenceStep::Hold { fingers: f1, duration: d1 }, DefinedSequenceStep::Hold { fingers: f2, duration: d2 }) => {
+                if f1 != f2 || d1 != d2 {
                     return false;
                 }
             }
@@ -263,16 +640,17 @@ fn are_gestures_conflicting(g1: &Gesture, g2: &Gesture) -> bool {
 impl Config {
     pub fn parse_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let content = std::fs::read_to_string(&path)?;
-        let main_config_raw: ConfigRaw = serde_yaml::from_str(&content)?;
+        Self::parse(&content, path)
+    }
+
+    /// Parses the content of a config file, imports are relative to `path`
+    pub fn parse<P: AsRef<Path>>(content: &str, path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let main_config_raw: ConfigRaw = serde_yaml::from_str(content)?;
 
         let options = main_config_raw.options.clone().unwrap_or_default();
-        let main_config = Config::from_raw(path, main_config_raw, &options)?;
+        let mut main_config = Config::from_raw(path, main_config_raw, &options)?;
 
-        let all_gestures = main_config.gestures
-            .iter()
-            .chain(main_config.application_gestures.by_title.iter().flat_map(|(_, gestures)| gestures))
-            .chain(main_config.application_gestures.by_class.iter().flat_map(|(_, gestures)| gestures))
-            .collect::<Vec<_>>();
+        let all_gestures = main_config.all_gestures().collect::<Vec<_>>();
 
         // Check for conflicting gestures
         for i in 0..main_config.gestures.len() {
@@ -289,7 +667,7 @@ impl Config {
         // Check for sequence steps with distance less than threshold
         for gesture in &all_gestures {
             for step in &gesture.sequence {
-                if let DefinedSequenceStep::Move { distance, .. } = step
+                if let DefinedSequenceStep::Move { distance, .. } | DefinedSequenceStep::Flick { distance, .. } = step
                     && let Some(distance) = distance
                     && *distance < main_config.options.move_threshold
                 {
@@ -301,9 +679,23 @@ impl Config {
             }
         }
 
+        let diagonal_moves = all_gestures.iter()
+            .flat_map(|gesture| &gesture.sequence)
+            .any(|step| matches!(step, DefinedSequenceStep::Move { direction, .. } | DefinedSequenceStep::Flick { direction, .. } if direction.is_diagonal()));
+
+        main_config.diagonal_moves = diagonal_moves;
+
         Ok(main_config)
     }
 
+    /// Global gestures followed by the application gestures
+    pub fn all_gestures(&self) -> impl Iterator<Item = &Gesture> {
+        self.gestures
+            .iter()
+            .chain(self.application_gestures.by_title.iter().flat_map(|(_, gestures)| gestures))
+            .chain(self.application_gestures.by_class.iter().flat_map(|(_, gestures)| gestures))
+    }
+
     pub fn get_config_path() -> Option<std::path::PathBuf> {
         if let Ok(xdg_config_home) = std::env::var("XDG_CONFIG_HOME") {
             Some(std::path::PathBuf::from(xdg_config_home).join("gest/config.yaml"))
@@ -0,0 +1,217 @@
+use std::io::{BufRead, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use crate::events::EventSender;
+
+/// Prefix of responses to failed requests
+const ERROR_PREFIX: &str = "error: ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Status,
+    Reload,
+    Pause,
+    Resume,
+    ListGestures,
+    ActiveWindow,
+    Trigger(String),
+    /// keeps the connection open and streams engine events as JSON lines
+    Subscribe,
+}
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (command, argument) = line.split_once(' ').map_or((line, ""), |(command, argument)| (command, argument.trim()));
+
+        let request = match command {
+            "status" => Request::Status,
+            "reload" => Request::Reload,
+            "pause" => Request::Pause,
+            "resume" => Request::Resume,
+            "list-gestures" => Request::ListGestures,
+            "active-window" => Request::ActiveWindow,
+            "subscribe" => Request::Subscribe,
+            "trigger" if !argument.is_empty() => return Ok(Request::Trigger(argument.to_string())),
+            "trigger" => return Err("trigger requires a gesture name".to_string()),
+            _ => return Err(format!("Unknown command: '{}'", command)),
+        };
+
+        if !argument.is_empty() {
+            return Err(format!("{} takes no arguments", command));
+        }
+        Ok(request)
+    }
+}
+
+/// Response text, or an error message
+pub type Response = Result<String, String>;
+
+pub type RequestSender = mpsc::Sender<(Request, oneshot::Sender<Response>)>;
+
+pub fn socket_path() -> PathBuf {
+    match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(xdg_runtime_dir) => Path::new(&xdg_runtime_dir).join("gest.sock"),
+        Err(_) => PathBuf::from("/tmp/gest.sock"),
+    }
+}
+
+/// The listening control socket, its file is removed when it is dropped
+pub struct ControlSocket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    /// Binds the socket, only the current user can connect to it
+    pub fn bind(path: &Path) -> std::io::Result<Self> {
+        // The lock file guarantees that a leftover socket belongs to a dead instance
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let socket = ControlSocket { listener, path: path.to_path_buf() };
+        // Sockets are created with the umask, which leaves the /tmp fallback open to other users
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        Ok(socket)
+    }
+
+    /// Accepts control connections, each one sends a single request line and receives the response
+    pub async fn serve(self, requests: RequestSender, events: EventSender) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let requests = requests.clone();
+            let events = events.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut line = String::new();
+                if let Err(e) = BufReader::new(reader).read_line(&mut line).await {
+                    log::debug!("Failed to read control request: {}", e);
+                    return;
+                }
+
+                let response = match Request::parse(&line) {
+                    Ok(Request::Subscribe) => {
+                        log::debug!("Control request: {:?}", Request::Subscribe);
+                        stream_events(&mut writer, events.subscribe()).await;
+                        return;
+                    }
+                    Ok(request) => {
+                        log::debug!("Control request: {:?}", request);
+                        let (reply, response) = oneshot::channel();
+                        if requests.send((request, reply)).await.is_err() {
+                            return;
+                        }
+                        response.await.unwrap_or_else(|_| Err("gest is shutting down".to_string()))
+                    }
+                    Err(e) => Err(e),
+                };
+
+                let text = match response {
+                    Ok(text) => text,
+                    Err(e) => format!("{}{}\n", ERROR_PREFIX, e),
+                };
+                if let Err(e) = writer.write_all(text.as_bytes()).await {
+                    log::debug!("Failed to write control response: {}", e);
+                }
+            });
+        }
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::debug!("Failed to remove control socket {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+async fn stream_events(writer: &mut (impl AsyncWriteExt + Unpin), mut events: broadcast::Receiver<crate::events::Event>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                log::warn!("Event subscriber is too slow, {} events were dropped", count);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        let Ok(mut line) = serde_json::to_string(&event) else {
+            continue;
+        };
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            // The subscriber disconnected
+            return;
+        }
+    }
+}
+
+/// Sends a request to the running instance and copies its response to the output as it arrives
+pub fn send_request(request: &str, output: &mut impl Write) -> Result<(), String> {
+    let path = socket_path();
+    let mut stream = std::os::unix::net::UnixStream::connect(&path)
+        .map_err(|e| format!("Failed to connect to {} (is gest running?): {}", path.display(), e))?;
+    stream.write_all(format!("{}\n", request).as_bytes())
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    for line in std::io::BufReader::new(stream).lines() {
+        let line = line.map_err(|e| format!("Failed to read response: {}", e))?;
+        if let Some(error) = line.strip_prefix(ERROR_PREFIX) {
+            return Err(error.to_string());
+        }
+        writeln!(output, "{}", line)
+            .and_then(|_| output.flush())
+            .map_err(|e| format!("Failed to write response: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        assert_eq!(Request::parse("status"), Ok(Request::Status));
+        assert_eq!(Request::parse("reload"), Ok(Request::Reload));
+        assert_eq!(Request::parse("pause"), Ok(Request::Pause));
+        assert_eq!(Request::parse("resume"), Ok(Request::Resume));
+        assert_eq!(Request::parse("list-gestures"), Ok(Request::ListGestures));
+        assert_eq!(Request::parse("active-window"), Ok(Request::ActiveWindow));
+        assert_eq!(Request::parse("subscribe"), Ok(Request::Subscribe));
+        assert_eq!(Request::parse("trigger Swipe left"), Ok(Request::Trigger("Swipe left".to_string())));
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        assert_eq!(Request::parse("  status \n"), Ok(Request::Status));
+        assert_eq!(Request::parse("trigger   Swipe left \n"), Ok(Request::Trigger("Swipe left".to_string())));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Request::parse(""), Err("Unknown command: ''".to_string()));
+        assert_eq!(Request::parse("stop"), Err("Unknown command: 'stop'".to_string()));
+        assert_eq!(Request::parse("Status"), Err("Unknown command: 'Status'".to_string()));
+        assert_eq!(Request::parse("trigger"), Err("trigger requires a gesture name".to_string()));
+        assert_eq!(Request::parse("trigger  "), Err("trigger requires a gesture name".to_string()));
+        assert_eq!(Request::parse("pause 5"), Err("pause takes no arguments".to_string()));
+        assert_eq!(Request::parse("list-gestures all"), Err("list-gestures takes no arguments".to_string()));
+    }
+
+    #[tokio::test]
+    async fn socket_is_private_and_removed_when_dropped() {
+        let path = std::env::temp_dir().join(format!("gest-test-{}.sock", std::process::id()));
+        let socket = ControlSocket::bind(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
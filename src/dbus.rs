@@ -0,0 +1,348 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::OnceLock;
+use zbus::blocking::Connection;
+use zbus::message::Flags;
+use zbus::names::{BusName, InterfaceName, MemberName};
+use zbus::zvariant::{Array, Dict, ObjectPath, Signature, Structure, StructureBuilder, Value};
+use zbus::Message;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bus {
+    #[default]
+    Session,
+    System,
+}
+
+/// D-Bus method call of a gesture, sent without waiting for a reply
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DBusCall {
+    #[serde(default)]
+    pub bus: Bus,
+    pub destination: String,
+    pub path: String,
+    pub interface: String,
+    pub method: String,
+    #[serde(default)]
+    pub args: Vec<serde_yaml::Value>,
+    /// D-Bus types of the arguments, e.g. `sx`, inferred from the YAML values if not set
+    pub signature: Option<String>,
+}
+
+impl DBusCall {
+    /// Checks the names and arguments, so that invalid calls are reported when the config is loaded
+    pub fn validate(&self) -> Result<(), String> {
+        BusName::try_from(self.destination.as_str()).map_err(|e| format!("Invalid destination '{}': {}", self.destination, e))?;
+        ObjectPath::try_from(self.path.as_str()).map_err(|e| format!("Invalid path '{}': {}", self.path, e))?;
+        InterfaceName::try_from(self.interface.as_str()).map_err(|e| format!("Invalid interface '{}': {}", self.interface, e))?;
+        MemberName::try_from(self.method.as_str()).map_err(|e| format!("Invalid method '{}': {}", self.method, e))?;
+        self.body()?;
+        Ok(())
+    }
+
+    /// Arguments of the call as a structure, whose fields are the message's arguments
+    fn body(&self) -> Result<Option<Structure<'static>>, String> {
+        let signatures = match &self.signature {
+            Some(signature) => {
+                let signature = Signature::try_from(format!("({})", signature).as_str())
+                    .map_err(|e| format!("Invalid signature '{}': {}", signature, e))?;
+                let Signature::Structure(fields) = signature else {
+                    unreachable!("Parenthesized signatures are structures");
+                };
+                fields.iter().cloned().collect()
+            }
+            None => self.args.iter().map(infer_signature).collect::<Vec<_>>(),
+        };
+        if signatures.len() != self.args.len() {
+            return Err(format!("Signature has {} types but there are {} arguments", signatures.len(), self.args.len()));
+        }
+        if self.args.is_empty() {
+            return Ok(None);
+        }
+
+        let mut builder = StructureBuilder::new();
+        for (arg, signature) in self.args.iter().zip(&signatures) {
+            builder = builder.append_field(to_value(arg, signature)?);
+        }
+        builder.build().map(Some).map_err(|e| e.to_string())
+    }
+
+    fn message(&self) -> Result<Message, Box<dyn std::error::Error>> {
+        let builder = Message::method_call(self.path.as_str(), self.method.as_str())?
+            .destination(self.destination.as_str())?
+            .interface(self.interface.as_str())?
+            .with_flags(Flags::NoReplyExpected)?;
+        Ok(match self.body()? {
+            Some(body) => builder.build(&body)?,
+            None => builder.build(&())?,
+        })
+    }
+}
+
+impl std::fmt::Display for DBusCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}.{}", self.destination, self.path, self.interface, self.method)
+    }
+}
+
+/// D-Bus type of a YAML value: strings, booleans, numbers, lists of strings and mappings to variants
+fn infer_signature(value: &serde_yaml::Value) -> Signature {
+    match value {
+        serde_yaml::Value::Bool(_) => Signature::Bool,
+        serde_yaml::Value::Number(n) if n.is_f64() => Signature::F64,
+        serde_yaml::Value::Number(n) if n.as_i64().is_some_and(|n| i32::try_from(n).is_ok()) => Signature::I32,
+        serde_yaml::Value::Number(n) if n.is_i64() => Signature::I64,
+        serde_yaml::Value::Number(_) => Signature::U64,
+        serde_yaml::Value::Sequence(items) => Signature::array(items.first().map_or(Signature::Str, infer_signature)),
+        serde_yaml::Value::Mapping(_) => Signature::dict(Signature::Str, Signature::Variant),
+        _ => Signature::Str,
+    }
+}
+
+fn to_value(value: &serde_yaml::Value, signature: &Signature) -> Result<Value<'static>, String> {
+    let invalid = || format!("Invalid argument {:?} for D-Bus type {}", value, signature);
+    let integer = || value.as_i64().ok_or_else(invalid);
+
+    Ok(match (signature, value) {
+        (Signature::Str, serde_yaml::Value::String(s)) => Value::from(s.clone()),
+        (Signature::ObjectPath, serde_yaml::Value::String(s)) => Value::from(ObjectPath::try_from(s.clone()).map_err(|_| invalid())?),
+        (Signature::Signature, serde_yaml::Value::String(s)) => Value::from(Signature::try_from(s.as_str()).map_err(|_| invalid())?),
+        (Signature::Bool, serde_yaml::Value::Bool(b)) => Value::from(*b),
+        (Signature::U8, _) => Value::from(u8::try_from(integer()?).map_err(|_| invalid())?),
+        (Signature::I16, _) => Value::from(i16::try_from(integer()?).map_err(|_| invalid())?),
+        (Signature::U16, _) => Value::from(u16::try_from(integer()?).map_err(|_| invalid())?),
+        (Signature::I32, _) => Value::from(i32::try_from(integer()?).map_err(|_| invalid())?),
+        (Signature::U32, _) => Value::from(u32::try_from(integer()?).map_err(|_| invalid())?),
+        (Signature::I64, _) => Value::from(integer()?),
+        (Signature::U64, _) => Value::from(value.as_u64().ok_or_else(invalid)?),
+        (Signature::F64, _) => Value::from(value.as_f64().ok_or_else(invalid)?),
+        (Signature::Variant, _) => Value::Value(Box::new(to_value(value, &infer_signature(value))?)),
+        (Signature::Array(element), serde_yaml::Value::Sequence(items)) => {
+            let mut array = Array::new(element);
+            for item in items {
+                array.append(to_value(item, element)?).map_err(|e| e.to_string())?;
+            }
+            Value::Array(array)
+        }
+        (Signature::Dict { key, value: value_signature }, serde_yaml::Value::Mapping(entries)) => {
+            let mut dict = Dict::new(key, value_signature);
+            for (k, v) in entries {
+                dict.append(to_value(k, key)?, to_value(v, value_signature)?).map_err(|e| e.to_string())?;
+            }
+            Value::Dict(dict)
+        }
+        _ => return Err(invalid()),
+    })
+}
+
+enum Request {
+    Connect(Bus),
+    Call(DBusCall),
+}
+
+/// Sends calls from a separate thread, so that connecting to a slow or missing bus doesn't hold up gesture recognition.
+/// The thread is started on first use.
+#[derive(Default)]
+pub struct DBus {
+    requests: OnceLock<Sender<Request>>,
+}
+
+impl DBus {
+    /// Queues a call, failures are logged by the D-Bus thread
+    pub fn call(&self, call: &DBusCall) -> Result<(), Box<dyn std::error::Error>> {
+        self.send(Request::Call(call.clone()))
+    }
+
+    /// Connects to a bus ahead of the first call on it
+    pub fn connect(&self, bus: Bus) -> Result<(), Box<dyn std::error::Error>> {
+        self.send(Request::Connect(bus))
+    }
+
+    fn send(&self, request: Request) -> Result<(), Box<dyn std::error::Error>> {
+        let requests = self.requests.get_or_init(|| {
+            let (sender, receiver) = channel();
+            std::thread::spawn(move || Self::run(receiver));
+            sender
+        });
+        requests.send(request).map_err(|_| "D-Bus thread stopped".into())
+    }
+
+    fn run(requests: Receiver<Request>) {
+        let mut connections = HashMap::new();
+        for request in requests {
+            match request {
+                Request::Connect(bus) => {
+                    if let Err(e) = Self::connection(&mut connections, bus) {
+                        log::warn!("Failed to connect to the {:?} bus: {}", bus, e);
+                    }
+                }
+                Request::Call(call) => {
+                    if let Err(e) = Self::send_call(&mut connections, &call) {
+                        log::error!("Failed to call {}: {}", call, e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn connection(connections: &mut HashMap<Bus, Connection>, bus: Bus) -> zbus::Result<&Connection> {
+        Ok(match connections.entry(bus) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let connection = match bus {
+                    Bus::Session => Connection::session()?,
+                    Bus::System => Connection::system()?,
+                };
+                log::info!("Connected to the {:?} bus", bus);
+                entry.insert(connection)
+            }
+        })
+    }
+
+    fn send_call(connections: &mut HashMap<Bus, Connection>, call: &DBusCall) -> Result<(), Box<dyn std::error::Error>> {
+        let message = call.message()?;
+        if let Err(e) = Self::connection(connections, call.bus)?.send(&message) {
+            // Reconnect on the next call, e.g. after the bus was restarted
+            connections.remove(&call.bus);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(value: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(value).unwrap()
+    }
+
+    fn call(args: &str, signature: Option<&str>) -> DBusCall {
+        DBusCall {
+            bus: Bus::Session,
+            destination: "org.example.Test".to_string(),
+            path: "/org/example/Test".to_string(),
+            interface: "org.example.Test".to_string(),
+            method: "Ping".to_string(),
+            args: serde_yaml::from_str(args).unwrap(),
+            signature: signature.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn signatures_are_inferred_from_yaml_values() {
+        assert_eq!(infer_signature(&yaml("text")), Signature::Str);
+        assert_eq!(infer_signature(&yaml("true")), Signature::Bool);
+        assert_eq!(infer_signature(&yaml("-5")), Signature::I32);
+        assert_eq!(infer_signature(&yaml("5000000000")), Signature::I64);
+        assert_eq!(infer_signature(&yaml("10000000000000000000")), Signature::U64);
+        assert_eq!(infer_signature(&yaml("0.5")), Signature::F64);
+        assert_eq!(infer_signature(&yaml("[1, 2]")), Signature::array(Signature::I32));
+        assert_eq!(infer_signature(&yaml("[]")), Signature::array(Signature::Str));
+        assert_eq!(infer_signature(&yaml("{a: 1}")), Signature::dict(Signature::Str, Signature::Variant));
+    }
+
+    #[test]
+    fn values_are_converted_to_their_signature() {
+        assert_eq!(to_value(&yaml("text"), &Signature::Str), Ok(Value::from("text")));
+        assert_eq!(to_value(&yaml("5"), &Signature::I64), Ok(Value::from(5i64)));
+        assert_eq!(to_value(&yaml("5"), &Signature::U8), Ok(Value::from(5u8)));
+        assert_eq!(to_value(&yaml("2"), &Signature::F64), Ok(Value::from(2.0)));
+        assert_eq!(to_value(&yaml("text"), &Signature::Variant), Ok(Value::Value(Box::new(Value::from("text")))));
+        assert_eq!(
+            to_value(&yaml("/org/example"), &Signature::ObjectPath),
+            Ok(Value::from(ObjectPath::try_from("/org/example").unwrap())),
+        );
+    }
+
+    #[test]
+    fn containers_are_converted_element_wise() {
+        let array = to_value(&yaml("[a, b]"), &Signature::array(Signature::Str)).unwrap();
+        assert_eq!(array, Value::from(vec!["a", "b"]));
+
+        let dict = to_value(&yaml("{volume: 0.5}"), &Signature::dict(Signature::Str, Signature::Variant)).unwrap();
+        let Value::Dict(dict) = dict else { panic!("expected a dict, got {:?}", dict) };
+        assert_eq!(dict.get::<&str, Value>(&"volume").unwrap(), Some(Value::from(0.5)));
+    }
+
+    #[test]
+    fn mismatched_values_are_rejected() {
+        assert!(to_value(&yaml("256"), &Signature::U8).is_err());
+        assert!(to_value(&yaml("-1"), &Signature::U64).is_err());
+        assert!(to_value(&yaml("text"), &Signature::Bool).is_err());
+        assert!(to_value(&yaml("relative"), &Signature::ObjectPath).is_err());
+        assert!(to_value(&yaml("[1, a]"), &Signature::array(Signature::I32)).is_err());
+    }
+
+    #[test]
+    fn signature_must_match_the_arguments() {
+        assert!(call("[1]", Some("x")).validate().is_ok());
+        assert!(call("[a, 1]", Some("s")).validate().is_err());
+        assert!(call("[a]", Some("(")).validate().is_err());
+        assert!(call("[a]", Some("x")).validate().is_err());
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut invalid = call("[]", None);
+        invalid.path = "relative".to_string();
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = call("[]", None);
+        invalid.interface = "NoDots".to_string();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn messages_expect_no_reply() {
+        let message = call("[a, 5]", Some("sx")).message().unwrap();
+        assert!(message.header().primary().flags().contains(Flags::NoReplyExpected));
+        assert_eq!(message.header().signature().to_string_no_parens(), "sx");
+        assert_eq!(message.body().deserialize::<(String, i64)>().unwrap(), ("a".to_string(), 5));
+    }
+
+    struct Test(std::sync::mpsc::Sender<(String, i64)>);
+
+    #[zbus::interface(name = "org.example.Test")]
+    impl Test {
+        fn ping(&self, text: String, count: i64) {
+            self.0.send((text, count)).unwrap();
+        }
+    }
+
+    /// Needs `dbus-daemon` in the PATH, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn calls_reach_the_session_bus() {
+        let mut daemon = std::process::Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("dbus-daemon should be installed");
+        let mut address = String::new();
+        std::io::BufRead::read_line(&mut std::io::BufReader::new(daemon.stdout.take().unwrap()), &mut address).unwrap();
+        let address = address.trim();
+
+        let (sender, receiver) = channel();
+        let _service = zbus::blocking::connection::Builder::address(address).unwrap()
+            .name("org.example.Test").unwrap()
+            .serve_at("/org/example/Test", Test(sender)).unwrap()
+            .build()
+            .unwrap();
+
+        // SAFETY: no other test reads or writes the environment
+        unsafe { std::env::set_var("DBUS_SESSION_BUS_ADDRESS", address) };
+        let dbus = DBus::default();
+        dbus.connect(Bus::Session).unwrap();
+        dbus.call(&call("[hello, 3]", Some("sx"))).unwrap();
+        let received = receiver.recv_timeout(std::time::Duration::from_secs(5));
+
+        daemon.kill().unwrap();
+        daemon.wait().unwrap();
+        assert_eq!(received, Ok(("hello".to_string(), 3)));
+    }
+}
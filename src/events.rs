@@ -0,0 +1,22 @@
+use tokio::sync::broadcast;
+use crate::Window;
+
+/// Engine events published to control socket subscribers, serialized as one JSON object per line
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    TouchDown { fingers: usize },
+    TouchUp { fingers: usize },
+    /// a step was added to the performed sequence
+    Step { step: String },
+    GestureMatched { name: String },
+    CommandExecuted { gesture: String, command: String },
+    KeysPressed { gesture: String, keys: String },
+    DbusCalled { gesture: String, call: String },
+    WindowChanged(Window),
+}
+
+pub type EventSender = broadcast::Sender<Event>;
+
+/// Number of events a slow subscriber may fall behind before it misses some
+pub const EVENT_BUFFER_SIZE: usize = 256;
@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+use std::process::Stdio;
+use std::sync::Mutex;
+use crate::config::Config;
+use crate::dbus::{DBus, DBusCall};
+use crate::keys::{KeyChord, Keyboard};
+
+/// Something a matched gesture does
+#[derive(Debug, Clone, PartialEq)]
+pub enum GestureAction {
+    /// shell command, run with `sh -c`
+    Command(String),
+    /// program and its arguments, run without a shell
+    Exec(Vec<String>),
+    Keys(KeyChord),
+    DBus(DBusCall),
+}
+
+impl std::fmt::Display for GestureAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GestureAction::Command(command) => write!(f, "{}", command),
+            GestureAction::Exec(argv) => write!(f, "{}", argv.join(" ")),
+            GestureAction::Keys(keys) => write!(f, "{}", keys),
+            GestureAction::DBus(call) => write!(f, "{}", call),
+        }
+    }
+}
+
+/// Environment variables describing the gesture, see `GEST_*` in the README
+pub type ActionEnv<'a> = &'a [(&'static str, String)];
+
+/// Runs the actions of matched gestures
+pub trait ActionExecutor: Send + Sync {
+    fn execute(&self, action: &GestureAction, env: ActionEnv) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Called when a config is loaded, to set up what its actions need without delaying the first gesture
+    fn prepare(&self, _config: &Config) {}
+}
+
+/// Runs commands and programs as child processes, presses keys through a uinput virtual keyboard and sends D-Bus calls
+#[derive(Default)]
+pub struct SystemExecutor {
+    keyboard: Keyboard,
+    dbus: DBus,
+}
+
+impl SystemExecutor {
+    /// Starts a program without waiting for it, its output is discarded
+    fn spawn(program: &str, args: &[String], env: ActionEnv) -> std::io::Result<()> {
+        std::process::Command::new(program)
+            .args(args)
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        Ok(())
+    }
+}
+
+impl ActionExecutor for SystemExecutor {
+    fn execute(&self, action: &GestureAction, env: ActionEnv) -> Result<(), Box<dyn std::error::Error>> {
+        match action {
+            GestureAction::Command(command) => Self::spawn("sh", &["-c".to_string(), command.clone()], env)?,
+            GestureAction::Exec(argv) => {
+                let (program, args) = argv.split_first().ok_or("Empty exec")?;
+                Self::spawn(program, args, env)?;
+            }
+            GestureAction::Keys(keys) => self.keyboard.press(keys)?,
+            GestureAction::DBus(call) => self.dbus.call(call)?,
+        }
+        Ok(())
+    }
+
+    fn prepare(&self, config: &Config) {
+        let uses_keys = config.all_gestures().any(|gesture| matches!(gesture.action, Some(GestureAction::Keys(_))));
+        if uses_keys {
+            self.keyboard.open();
+        }
+
+        let buses = config.all_gestures()
+            .filter_map(|gesture| match &gesture.action {
+                Some(GestureAction::DBus(call)) => Some(call.bus),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        for bus in buses {
+            if let Err(e) = self.dbus.connect(bus) {
+                log::error!("Failed to connect to the {:?} bus: {}", bus, e);
+            }
+        }
+    }
+}
+
+/// An action with the environment it was executed with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedAction {
+    pub action: GestureAction,
+    pub env: Vec<(&'static str, String)>,
+}
+
+/// Records actions instead of executing them, e.g. for tests and simulations
+#[derive(Debug, Default)]
+pub struct RecordingExecutor {
+    executed: Mutex<Vec<ExecutedAction>>,
+}
+
+impl RecordingExecutor {
+    /// Actions executed since the last call
+    pub fn take(&self) -> Vec<ExecutedAction> {
+        std::mem::take(&mut self.executed.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl ActionExecutor for RecordingExecutor {
+    fn execute(&self, action: &GestureAction, env: ActionEnv) -> Result<(), Box<dyn std::error::Error>> {
+        let executed = ExecutedAction { action: action.clone(), env: env.to_vec() };
+        self.executed.lock().unwrap_or_else(|e| e.into_inner()).push(executed);
+        Ok(())
+    }
+}
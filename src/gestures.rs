@@ -1,15 +1,19 @@
 // TODO: fix issue with reassigning slots when fingers are lifted and new ones are added
 
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
 use arc_swap::ArcSwap;
 
-use crate::config::{Config, Direction, Edge, Gesture, RepeatMode};
-use crate::Window;
-use crate::sequence_step::{DefinedSequenceStep, PerformedSequenceStep};
+use crate::config::{Config, Direction, Edge, Gesture, PinchDirection, RepeatMode, RotationDirection};
+use crate::events::{Event, EventSender};
+use crate::executor::{ActionExecutor, GestureAction, SystemExecutor};
+use crate::window_monitor::WindowContext;
+use crate::sequence_step::{DefinedSequenceStep, PerformedSequenceStep, StepTime};
 
+/// Finger position in touchpad units
 #[derive(Debug, Clone, Copy)]
 pub struct Position {
     pub x: u16,
@@ -25,9 +29,12 @@ impl Position {
     }
 }
 
+/// Positions of the fingers on the touchpad by slot, at one point in time
 #[derive(Default, Debug, Clone)]
 pub struct State {
     pub positions: HashMap<u8, Position>,
+    /// timestamp of the input event that produced this state
+    pub time: Duration,
 }
 
 impl State {
@@ -46,15 +53,112 @@ impl State {
             y: (sum_y / count) as u16,
         })
     }
+
+    /// Average distance of all fingers from the centroid
+    pub fn spread(&self) -> Option<f32> {
+        let centroid = self.centroid()?;
+        let sum = self.positions.values().map(|pos| {
+            let dx = pos.x as f32 - centroid.x as f32;
+            let dy = pos.y as f32 - centroid.y as f32;
+            (dx * dx + dy * dy).sqrt()
+        }).sum::<f32>();
+        Some(sum / self.positions.len() as f32)
+    }
+
+    /// Average angle in degrees (positive is clockwise) by which the fingers turned around the centroid since `previous`
+    pub fn rotation_since(&self, previous: &State) -> Option<f32> {
+        let centroid = self.centroid()?;
+        let previous_centroid = previous.centroid()?;
+        let angle = |pos: &Position, center: &Position| (pos.y as f32 - center.y as f32).atan2(pos.x as f32 - center.x as f32);
+
+        let deltas = self.positions.iter()
+            .filter_map(|(slot, pos)| {
+                let previous_pos = previous.positions.get(slot)?;
+                let delta = angle(pos, &centroid) - angle(previous_pos, &previous_centroid);
+                // Wrap into (-PI, PI] so that crossing the negative x axis doesn't count as a full turn
+                Some((delta + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI)
+            })
+            .collect::<Vec<_>>();
+
+        if deltas.is_empty() {
+            return None;
+        }
+        Some((deltas.iter().sum::<f32>() / deltas.len() as f32).to_degrees())
+    }
+
+    fn has_same_slots(&self, other: &State) -> bool {
+        self.positions.len() == other.positions.len()
+            && self.positions.keys().all(|slot| other.positions.contains_key(slot))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Begin,
+    Update,
+    End,
+}
+
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Begin => "begin",
+            Phase::Update => "update",
+            Phase::End => "end",
+        }
+    }
+}
+
+/// How many times farther the centroid must travel than the fingers spread, close or turn for the fingers to be moving,
+/// the centroid also travels when pinching or rotating with a finger anchored
+const MOVE_DOMINANCE: f32 = 2.0;
+
+/// How long the fingers must rest to record a hold step, shorter rests between moves are not deliberate holds
+const RECORDED_HOLD_MIN: Duration = Duration::from_millis(500);
+/// Precision of the recorded duration of a hold step
+const RECORDED_HOLD_RESOLUTION: Duration = Duration::from_millis(250);
+
+/// Sequence of steps performed between touching the touchpad and lifting all fingers
+#[derive(Debug, Clone)]
+pub struct PerformedGesture {
+    pub sequence: Vec<PerformedSequenceStep>,
+    pub edge: Option<Edge>,
+}
+
+/// A gesture with `repeat_mode: continuous` which began and is following the fingers until they are lifted or their number changes
+#[derive(Debug)]
+struct ContinuousGesture {
+    gesture: Gesture,
+    /// environment of the gesture's commands at the time it began
+    env: Vec<(&'static str, String)>,
+    tracking: Tracking,
+    /// value of the last step at which the gesture is complete, used to compute progress
+    target: f32,
+    /// value of the last step when the last phase was run
+    value: f32,
 }
 
+/// How a continuous gesture follows the value of its last step, signed so that moving back decreases it
+#[derive(Debug)]
+enum Tracking {
+    /// displacement of the centroid along the move's direction, from where the step started
+    Move { origin: Position, direction: Direction },
+    /// change of the fingers' spread relative to the start of the pinch, positive in the pinch's direction
+    Pinch { start_spread: f32, direction: PinchDirection },
+    /// angle the fingers turned since the start of the rotation, positive in the rotation's direction
+    Rotate { angle: f32, direction: RotationDirection },
+    /// value of the last performed step while it is a hold
+    Hold,
+}
+
+/// Horizontal and vertical extent in touchpad units
 #[derive(Debug, Clone, Copy)]
 pub struct MoveThresholdUnits {
     pub x: u16,
     pub y: u16,
 }
 
-#[derive(Debug)]
+/// Recognizes the steps performed on the touchpad and runs the gestures of the config whose sequence they match
 pub struct GesturesEngine {
     pub config: Arc<ArcSwap<Config>>,
     /// positions of fingers in the previous update
@@ -67,15 +171,55 @@ pub struct GesturesEngine {
     repeat_mode: RepeatMode,
     move_threshold_units: MoveThresholdUnits,
     touchpad_size: MoveThresholdUnits,
-    active_window: Arc<ArcSwap<Window>>,
+    active_window: Arc<dyn WindowContext>,
+    executor: Arc<dyn ActionExecutor>,
     previous_direction: Direction,
     starting_edge: Option<Edge>,
     gesture_in_progress: bool,
     state_directions: HashMap<u8, Direction>,
+    /// spread of the fingers at the start of the current pinch step
+    pinch_start_spread: Option<f32>,
+    /// scale relative to `pinch_start_spread` at which a pinch was last registered
+    pinch_trigger_scale: f32,
+    /// angle the fingers turned since the start of the current rotation step
+    rotation_angle: f32,
+    /// `rotation_angle` at which a rotation was last registered
+    rotation_trigger_angle: f32,
+    /// time since which the fingers have been resting inside the move ellipse
+    hold_start: Option<Duration>,
+    /// whether a hold gesture already fired for the fingers currently down
+    hold_matched: bool,
+    /// largest set of fingers down at the same time since the first one touched down
+    tap_slots: HashSet<u8>,
+    /// taps performed so far, each as a touch down and touch up step
+    tap_sequence: Vec<PerformedSequenceStep>,
+    tap_edge: Option<Edge>,
+    last_tap: Option<Duration>,
+    /// time of the last tap, kept after pending taps ran so that consecutive taps count as repeats
+    previous_tap: Option<Duration>,
+    /// time when the first finger touched down
+    touch_down_time: Duration,
+    /// time at the start of the current sequence step
+    sequence_step_start_time: Duration,
+    /// time at the start of the current pinch step
+    pinch_start_time: Duration,
+    /// time at the start of the current rotation step
+    rotation_start_time: Duration,
+    /// smoothed speed of the centroid, in touchpad sizes per second
+    centroid_velocity: f32,
+    continuous_gestures: Vec<ContinuousGesture>,
+    /// number of times the last run gestures ran since the fingers touched down, or since the first of consecutive taps
+    repeat_counts: RefCell<HashMap<String, u32>>,
+    /// input is ignored while paused
+    paused: bool,
+    events: EventSender,
+    /// performed gestures are collected instead of being matched while recording
+    recording: bool,
+    recorded: Vec<PerformedGesture>,
 }
 
 impl GesturesEngine {
-    pub fn new(config: Arc<ArcSwap<Config>>, active_window: Arc<ArcSwap<Window>>, move_threshold_units: MoveThresholdUnits, touchpad_size: MoveThresholdUnits) -> Self {
+    pub fn new(config: Arc<ArcSwap<Config>>, active_window: Arc<dyn WindowContext>, move_threshold_units: MoveThresholdUnits, touchpad_size: MoveThresholdUnits, events: EventSender) -> Self {
         Self {
             config,
             previous_state: State::default(),
@@ -86,10 +230,33 @@ impl GesturesEngine {
             move_threshold_units,
             touchpad_size,
             active_window,
+            executor: Arc::new(SystemExecutor::default()),
             previous_direction: Direction::None,
             starting_edge: None,
             gesture_in_progress: false,
             state_directions: HashMap::new(),
+            pinch_start_spread: None,
+            pinch_trigger_scale: 1.0,
+            rotation_angle: 0.0,
+            rotation_trigger_angle: 0.0,
+            hold_start: None,
+            hold_matched: false,
+            tap_slots: HashSet::new(),
+            tap_sequence: Vec::new(),
+            tap_edge: None,
+            last_tap: None,
+            previous_tap: None,
+            touch_down_time: Duration::ZERO,
+            sequence_step_start_time: Duration::ZERO,
+            pinch_start_time: Duration::ZERO,
+            rotation_start_time: Duration::ZERO,
+            centroid_velocity: 0.0,
+            continuous_gestures: Vec::new(),
+            repeat_counts: RefCell::new(HashMap::new()),
+            paused: false,
+            events,
+            recording: false,
+            recorded: Vec::new(),
         }
     }
 
@@ -109,13 +276,67 @@ impl GesturesEngine {
         }
     }
 
-    fn handle_lift_and_cleanup(&mut self) {
-        if self.repeat_mode == RepeatMode::None {
-            self.match_gestures(RepeatMode::None);
+    fn handle_lift_and_cleanup(&mut self, now: Duration) {
+        // Fingers resting longer than the time between taps are a press rather than a tap
+        let tap_interval = self.config.load().options.tap_interval;
+        let is_tap = !self.hold_matched
+            && now <= self.touch_down_time + tap_interval
+            && !self.tap_slots.is_empty()
+            && self.performed_sequence.iter().all(|step| matches!(step, PerformedSequenceStep::TouchUp { .. }));
+
+        if self.recording {
+            self.record_performed_gesture(is_tap, now);
+        } else if self.repeat_mode == RepeatMode::None {
+            if is_tap {
+                self.register_tap(now);
+            } else {
+                self.flush_taps();
+                self.match_gestures(RepeatMode::None);
+            }
         } else {
             self.repeat_mode = RepeatMode::None;
         }
+        self.end_continuous_gestures();
+        self.reset();
+    }
+
+    fn record_performed_gesture(&mut self, is_tap: bool, now: Duration) {
+        let sequence = if is_tap {
+            vec![
+                PerformedSequenceStep::TouchDown { slots: self.tap_slots.clone(), time: StepTime::at(self.touch_down_time) },
+                PerformedSequenceStep::TouchUp { slots: self.tap_slots.clone(), time: StepTime::at(now) },
+            ]
+        } else {
+            let trailing_count = self.performed_sequence.iter()
+                .rev()
+                .take_while(|step| matches!(step, PerformedSequenceStep::TouchDown { .. } | PerformedSequenceStep::TouchUp { .. }))
+                .count();
+            self.performed_sequence[..self.performed_sequence.len() - trailing_count].to_vec()
+        };
+
+        if !sequence.is_empty() {
+            self.recorded.push(PerformedGesture { sequence, edge: self.starting_edge });
+        }
+    }
 
+    /// Collects performed gestures instead of running the matching ones, see [`GesturesEngine::take_recorded`]
+    pub fn start_recording(&mut self) {
+        self.recording = true;
+    }
+
+    /// Gestures performed since the last call while recording
+    pub fn take_recorded(&mut self) -> Vec<PerformedGesture> {
+        std::mem::take(&mut self.recorded)
+    }
+
+    /// Replaces the executor running the actions of matched gestures, which runs commands and presses keys by default
+    pub fn with_executor(mut self, executor: Arc<dyn ActionExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Forgets the gesture in progress
+    fn reset(&mut self) {
         self.previous_state.positions.clear();
         self.touch_down_state.positions.clear();
         self.sequence_step_start_state.positions.clear();
@@ -124,21 +345,129 @@ impl GesturesEngine {
         self.starting_edge = None;
         self.gesture_in_progress = false;
         self.state_directions.clear();
+        self.pinch_start_spread = None;
+        self.pinch_trigger_scale = 1.0;
+        self.rotation_angle = 0.0;
+        self.rotation_trigger_angle = 0.0;
+        self.hold_start = None;
+        self.hold_matched = false;
+        self.tap_slots.clear();
+        self.centroid_velocity = 0.0;
+        self.repeat_mode = RepeatMode::None;
+    }
+
+    /// Stops recognizing gestures until [`GesturesEngine::resume`], the gesture in progress and pending taps are dropped
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.end_continuous_gestures();
+        self.reset();
+        self.tap_sequence.clear();
+        self.tap_edge = None;
+        self.last_tap = None;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Runs the active gesture with the given name as if it was performed, returns false if there is no such gesture
+    pub fn trigger(&self, name: &str) -> bool {
+        let config = self.config.load();
+        let Some(gesture) = self.active_gestures(&config).into_iter().find(|gesture| gesture.name == name) else {
+            return false;
+        };
+
+        log::debug!("Triggered gesture: {:?}", gesture.name);
+        // Triggers only count as repeats of a gesture performed meanwhile
+        if self.previous_state.positions.is_empty() {
+            self.repeat_counts.borrow_mut().clear();
+        }
+        self.count_repeats([gesture]);
+        self.run_gesture(gesture, &[], None, &config);
+        // A triggered continuous gesture has no fingers to follow
+        if gesture.repeat_mode.contains(RepeatMode::Continuous) {
+            let env = self.gesture_env(gesture, &[], None);
+            self.run_phase(gesture, &env, Phase::End, 0.0, 0.0, Self::continuous_target(gesture, &config));
+        }
+        true
     }
 
+    fn emit(&self, event: Event) {
+        // Sending only fails if nobody is subscribed
+        let _ = self.events.send(event);
+    }
+
+    fn push_step(&mut self, step: PerformedSequenceStep) {
+        // A hold which no gesture continues with this step would keep the sequence from matching anything
+        if !self.recording
+            && matches!(step, PerformedSequenceStep::Move { .. } | PerformedSequenceStep::Pinch { .. } | PerformedSequenceStep::Rotate { .. })
+            && matches!(self.performed_sequence.last(), Some(PerformedSequenceStep::Hold { .. }))
+            && !self.expects_after(&step)
+        {
+            let hold = self.performed_sequence.pop();
+            log::debug!("Dropped {:?}, no gesture continues it with {:?}", hold, step);
+        }
+
+        self.emit(Event::Step { step: format!("{:?}", step) });
+        self.performed_sequence.push(step);
+    }
+
+    /// Processes the touchpad state after an input frame, states must be passed in chronological order
     pub fn update_state(&mut self, state: State) {
+        if self.paused {
+            return;
+        }
+
+        let fingers = state.positions.len();
+        let previous_fingers = self.previous_state.positions.len();
+        if fingers > previous_fingers {
+            self.emit(Event::TouchDown { fingers });
+        } else if fingers < previous_fingers {
+            self.emit(Event::TouchUp { fingers });
+        }
+        if fingers != previous_fingers {
+            self.end_continuous_gestures();
+        }
+
         let config = self.config.load();
+        let now = state.time;
 
         if state.positions.is_empty() {
-            self.handle_lift_and_cleanup();
+            self.handle_lift_and_cleanup(now);
             return;
         }
 
+        if self.previous_state.positions.is_empty() {
+            self.touch_down_time = now;
+            self.sequence_step_start_time = now;
+
+            if self.previous_tap.is_none_or(|previous_tap| now > previous_tap + config.options.tap_interval) {
+                self.repeat_counts.borrow_mut().clear();
+            }
+            if let Some(last_tap) = self.last_tap
+                && now > last_tap + config.options.tap_interval
+            {
+                self.flush_taps();
+            }
+        }
+
+        if state.positions.len() > self.tap_slots.len() {
+            self.tap_slots = state.positions.keys().cloned().collect();
+        }
+
         for (slot, pos) in &state.positions {
             self.touch_down_state.positions.entry(*slot).or_insert(*pos);
             self.sequence_step_start_state.positions.entry(*slot).or_insert(*pos);
         }
 
+        if !state.has_same_slots(&self.previous_state) {
+            self.hold_start = Some(now);
+        }
+
         // Determine starting edge if not already set
         if self.performed_sequence.is_empty() {
             let mut edges = self.touch_down_state.positions.values().map(|pos| self.at_edge(pos, &config));
@@ -154,10 +483,11 @@ impl GesturesEngine {
             .map(|(slot, _)| slot)
             .collect::<Vec<_>>();
         for slot in lifted_slots {
-            if let Some(PerformedSequenceStep::TouchUp { slots }) = self.performed_sequence.last_mut() {
+            if let Some(PerformedSequenceStep::TouchUp { slots, time }) = self.performed_sequence.last_mut() {
                 slots.insert(slot);
+                time.end = now;
             } else if self.repeat_mode == RepeatMode::None {
-                self.performed_sequence.push(PerformedSequenceStep::TouchUp { slots: HashSet::from([slot]) });
+                self.push_step(PerformedSequenceStep::TouchUp { slots: HashSet::from([slot]), time: StepTime::at(now) });
                 // Reset start positions for all slots
                 for (slot, pos) in &state.positions {
                     self.touch_down_state.positions.insert(*slot, *pos);
@@ -165,17 +495,20 @@ impl GesturesEngine {
             }
         }
 
+        self.update_velocity(&state);
+
         if let Some(centroid) = state.centroid() {
             let touch_down_centroid = self.touch_down_state.centroid().unwrap();
 
             let edge = self.at_edge(&touch_down_centroid, &config);
 
-            let direction = self.point_side_in_ellipse(&centroid, &touch_down_centroid);
+            let direction = self.point_side_in_ellipse(&centroid, &touch_down_centroid, config.diagonal_moves);
             if direction != self.previous_direction {
                 // New sequence step, reset start positions
                 for (slot, pos) in &state.positions {
                     self.sequence_step_start_state.positions.insert(*slot, *pos);
                 }
+                self.sequence_step_start_time = now;
 
                 if let Some(PerformedSequenceStep::Move { direction: dir, .. }) = self.performed_sequence.last_mut()
                     && *dir != direction
@@ -186,28 +519,31 @@ impl GesturesEngine {
             }
             self.previous_direction = direction;
 
-            if self.point_outside_of_ellipse(&centroid, &touch_down_centroid, edge.is_some(), &config) {
+            let outside = self.point_outside_of_ellipse(&centroid, &touch_down_centroid, edge.is_some(), &config);
+            if outside {
+                self.hold_start = Some(now);
+            }
+            if outside && !self.is_deforming(&state) {
                 for slot in state.positions.keys() {
                     self.state_directions.insert(*slot, direction);
                 }
 
                 let distance = centroid.distance(&self.sequence_step_start_state.centroid().unwrap());
-                let (distance, size) = match direction {
-                    Direction::Up | Direction::Down => (distance.y, self.touchpad_size.y),
-                    Direction::Left | Direction::Right => (distance.x, self.touchpad_size.x),
-                    Direction::None => return,
+                let Some(norm) = self.normalized_distance(&distance, direction) else {
+                    return;
                 };
 
                 let slots = state.positions.keys().cloned().collect::<HashSet<u8>>();
 
-                let norm = distance as f32 / size as f32;
-                if let Some(PerformedSequenceStep::Move { slots: s, direction: dir, distance: dst }) = self.performed_sequence.last_mut()
+                if let Some(PerformedSequenceStep::Move { slots: s, direction: dir, distance: dst, time, .. }) = self.performed_sequence.last_mut()
                     && *dir == direction
                 {
                     *s = slots;
                     *dst = norm;
+                    time.end = now;
                 } else {
-                    self.performed_sequence.push(PerformedSequenceStep::Move { slots, direction, distance: norm });
+                    let time = StepTime { start: self.sequence_step_start_time, end: now };
+                    self.push_step(PerformedSequenceStep::Move { slots, direction, distance: norm, velocity: self.centroid_velocity, time });
                 }
 
                 for (&slot, pos) in &state.positions {
@@ -225,28 +561,41 @@ impl GesturesEngine {
         for (&slot, pos) in &state.positions {
             if let Some((sequence_step_start_position, direction)) = self.sequence_step_start_state.positions.get(&slot).zip(self.state_directions.get(&slot)) {
                 let distance = pos.distance(sequence_step_start_position);
-                let (distance, size) = match direction {
-                    Direction::Up | Direction::Down => (distance.y, self.touchpad_size.y),
-                    Direction::Left | Direction::Right => (distance.x, self.touchpad_size.x),
-                    Direction::None => continue,
+                let Some(norm) = self.normalized_distance(&distance, *direction) else {
+                    continue;
                 };
 
                 match self.performed_sequence.last_mut() {
-                    Some(PerformedSequenceStep::Move { slots, direction: dir, distance: dst }) if dir == direction => {
+                    Some(PerformedSequenceStep::Move { slots, direction: dir, distance: dst, time, .. }) if dir == direction => {
                         slots.insert(slot);
-                        *dst = dst.max(distance as f32 / size as f32);
+                        if norm > *dst {
+                            *dst = norm;
+                            time.end = now;
+                        }
                     }
                     _ => {}
                 }
             }
         }
 
+        // Keep the peak velocity of the move step in progress
+        if let Some(PerformedSequenceStep::Move { direction, velocity, .. }) = self.performed_sequence.last_mut()
+            && *direction == self.previous_direction
+        {
+            *velocity = velocity.max(self.centroid_velocity);
+        }
+
+        self.update_pinch(&state, &config, now);
+        self.update_rotation(&state, &config, now);
+        self.update_continuous_gestures(&state, &config);
+
         if state.positions.len() > self.previous_state.positions.len() && !self.performed_sequence.is_empty() {
             let new_slot = *state.positions.keys().find(|k| !self.previous_state.positions.contains_key(k)).unwrap();
-            if let Some(PerformedSequenceStep::TouchDown { slots }) = self.performed_sequence.last_mut() {
+            if let Some(PerformedSequenceStep::TouchDown { slots, time }) = self.performed_sequence.last_mut() {
                 slots.insert(new_slot);
+                time.end = now;
             } else {
-                self.performed_sequence.push(PerformedSequenceStep::TouchDown { slots: HashSet::from([new_slot]) });
+                self.push_step(PerformedSequenceStep::TouchDown { slots: HashSet::from([new_slot]), time: StepTime::at(now) });
             }
 
             // Check for repeated gestures
@@ -258,6 +607,277 @@ impl GesturesEngine {
         self.previous_state = state;
     }
 
+    /// Whether the fingers moved relative to each other rather than together since the move reference positions
+    fn is_deforming(&self, state: &State) -> bool {
+        if state.positions.len() < 2 {
+            return false;
+        }
+        let (Some(centroid), Some(spread)) = (state.centroid(), state.spread()) else {
+            return false;
+        };
+        let (Some(reference_centroid), Some(reference_spread)) = (self.touch_down_state.centroid(), self.touch_down_state.spread()) else {
+            return false;
+        };
+
+        // Distance the fingers travelled around the centroid when rotating
+        let arc = state.rotation_since(&self.touch_down_state).map_or(0.0, |angle| angle.to_radians().abs() * spread);
+
+        let travel = (centroid.x as f32 - reference_centroid.x as f32).hypot(centroid.y as f32 - reference_centroid.y as f32);
+        travel < (spread - reference_spread).abs().max(arc) * MOVE_DOMINANCE
+    }
+
+    fn update_velocity(&mut self, state: &State) {
+        if !state.has_same_slots(&self.previous_state) || state.time <= self.previous_state.time {
+            self.centroid_velocity = 0.0;
+            return;
+        }
+
+        let Some((centroid, previous_centroid)) = state.centroid().zip(self.previous_state.centroid()) else {
+            return;
+        };
+        let distance = centroid.distance(&previous_centroid);
+        let nx = distance.x as f32 / self.touchpad_size.x as f32;
+        let ny = distance.y as f32 / self.touchpad_size.y as f32;
+        let elapsed = (state.time - self.previous_state.time).as_secs_f32();
+
+        // Average with the previous value to smooth out jitter between single frames
+        let velocity = (nx * nx + ny * ny).sqrt() / elapsed;
+        self.centroid_velocity = (self.centroid_velocity + velocity) / 2.0;
+    }
+
+    fn update_pinch(&mut self, state: &State, config: &Config, now: Duration) {
+        // Pinches are only tracked while the same fingers stay on the touchpad and the centroid is not moving,
+        // a threshold of 0 would register a pinch on every frame
+        if config.options.pinch_threshold <= 0.0
+            || state.positions.len() < 2
+            || !state.has_same_slots(&self.previous_state)
+            || matches!(self.performed_sequence.last(), Some(PerformedSequenceStep::Move { .. }))
+        {
+            self.pinch_start_spread = None;
+            self.pinch_trigger_scale = 1.0;
+            return;
+        }
+
+        let Some(spread) = state.spread() else {
+            return;
+        };
+        let start_spread = match self.pinch_start_spread {
+            Some(start_spread) => start_spread,
+            None => {
+                self.pinch_start_spread = Some(spread);
+                self.pinch_start_time = self.previous_state.time;
+                spread
+            }
+        };
+        if start_spread <= 0.0 {
+            self.pinch_start_spread = None;
+            return;
+        }
+
+        let mut scale = spread / start_spread;
+        let relative_scale = scale / self.pinch_trigger_scale;
+        let direction = if relative_scale <= 1.0 - config.options.pinch_threshold {
+            PinchDirection::In
+        } else if relative_scale >= 1.0 + config.options.pinch_threshold {
+            PinchDirection::Out
+        } else {
+            // Below threshold, only extend the pinch step in progress
+            if let Some(PerformedSequenceStep::Pinch { direction, scale: scl, time, .. }) = self.performed_sequence.last_mut()
+                && match direction {
+                    PinchDirection::In => scale < *scl,
+                    PinchDirection::Out => scale > *scl,
+                }
+            {
+                *scl = scale;
+                time.end = now;
+            }
+            return;
+        };
+
+        let slots = state.positions.keys().cloned().collect::<HashSet<u8>>();
+        if let Some(PerformedSequenceStep::Pinch { slots: s, direction: dir, scale: scl, time }) = self.performed_sequence.last_mut()
+            && *dir == direction
+        {
+            *s = slots;
+            *scl = scale;
+            time.end = now;
+        } else {
+            // New pinch step, measure it from where the previous one was last registered
+            let new_start_spread = start_spread * self.pinch_trigger_scale;
+            self.pinch_start_spread = Some(new_start_spread);
+            scale = spread / new_start_spread;
+            let time = StepTime { start: self.pinch_start_time, end: now };
+            self.push_step(PerformedSequenceStep::Pinch { slots, direction, scale, time });
+        }
+        self.pinch_trigger_scale = scale;
+        self.pinch_start_time = now;
+        self.hold_start = Some(now);
+
+        self.match_gestures(RepeatMode::Slide);
+    }
+
+    fn update_rotation(&mut self, state: &State, config: &Config, now: Duration) {
+        // Rotations are only tracked while the same fingers stay on the touchpad and the centroid is not moving,
+        // a threshold of 0 would register a clockwise rotation on every frame
+        if config.options.rotation_threshold <= 0.0
+            || state.positions.len() < 2
+            || !state.has_same_slots(&self.previous_state)
+            || matches!(self.performed_sequence.last(), Some(PerformedSequenceStep::Move { .. }))
+        {
+            self.rotation_angle = 0.0;
+            self.rotation_trigger_angle = 0.0;
+            self.rotation_start_time = now;
+            return;
+        }
+
+        let Some(delta) = state.rotation_since(&self.previous_state) else {
+            return;
+        };
+        self.rotation_angle += delta;
+
+        let relative_angle = self.rotation_angle - self.rotation_trigger_angle;
+        let direction = if relative_angle >= config.options.rotation_threshold {
+            RotationDirection::Clockwise
+        } else if relative_angle <= -config.options.rotation_threshold {
+            RotationDirection::CounterClockwise
+        } else {
+            // Below threshold, only extend the rotation step in progress
+            if let Some(PerformedSequenceStep::Rotate { direction, angle, time, .. }) = self.performed_sequence.last_mut() {
+                let current_angle = match direction {
+                    RotationDirection::Clockwise => self.rotation_angle,
+                    RotationDirection::CounterClockwise => -self.rotation_angle,
+                };
+                if current_angle > *angle {
+                    *angle = current_angle;
+                    time.end = now;
+                }
+            }
+            return;
+        };
+
+        let slots = state.positions.keys().cloned().collect::<HashSet<u8>>();
+        if let Some(PerformedSequenceStep::Rotate { slots: s, direction: dir, angle, time }) = self.performed_sequence.last_mut()
+            && *dir == direction
+        {
+            *s = slots;
+            *angle = self.rotation_angle.abs();
+            time.end = now;
+        } else {
+            // New rotation step, measure it from where the previous one was last registered
+            self.rotation_angle = relative_angle;
+            let time = StepTime { start: self.rotation_start_time, end: now };
+            self.push_step(PerformedSequenceStep::Rotate { slots, direction, angle: relative_angle.abs(), time });
+        }
+        self.rotation_trigger_angle = self.rotation_angle;
+        self.rotation_start_time = now;
+        self.hold_start = Some(now);
+
+        self.match_gestures(RepeatMode::Slide);
+    }
+
+    /// Time at which [`GesturesEngine::update_timeout`] has to be called if no input arrives before then
+    pub fn next_timeout(&self) -> Option<Duration> {
+        let config = self.config.load();
+
+        // Pending taps wait for the next tap only while no fingers are down
+        if self.previous_state.positions.is_empty() {
+            return self.last_tap.map(|last_tap| last_tap + config.options.tap_interval);
+        }
+
+        let hold_start = self.hold_start?;
+        let held = match self.performed_sequence.last() {
+            Some(PerformedSequenceStep::Hold { time, .. }) => time.duration(),
+            _ => Duration::ZERO,
+        };
+        if self.recording {
+            // Long enough rests are recorded as holds, whose duration is measured in increments
+            return Some(hold_start + (held + RECORDED_HOLD_RESOLUTION).max(RECORDED_HOLD_MIN));
+        }
+        self.expected_hold_durations(&config)
+            .into_iter()
+            .find(|duration| *duration > held)
+            .map(|duration| hold_start + duration)
+    }
+
+    /// Sorted durations of the holds which active gestures expect as their next step, with the fingers currently down
+    fn expected_hold_durations(&self, config: &Config) -> Vec<Duration> {
+        let fingers = self.previous_state.positions.len();
+        // A hold in progress is extended, so it is not part of the steps before it
+        let performed = match self.performed_sequence.last() {
+            Some(PerformedSequenceStep::Hold { .. }) => &self.performed_sequence[..self.performed_sequence.len() - 1],
+            _ => &self.performed_sequence[..],
+        };
+
+        let mut durations = self.gestures_continuing(config, performed)
+            .filter_map(|gesture| match gesture.sequence[performed.len()] {
+                DefinedSequenceStep::Hold { fingers: f, duration } if f as usize == fingers => Some(duration),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        durations.sort();
+        durations.dedup();
+        durations
+    }
+
+    /// Whether an active gesture continues the performed sequence with a step which `step` can become
+    fn expects_after(&self, step: &PerformedSequenceStep) -> bool {
+        let config = self.config.load();
+        self.gestures_continuing(&config, &self.performed_sequence)
+            .any(|gesture| gesture.sequence[self.performed_sequence.len()].can_become(step))
+    }
+
+    /// Active gestures from the starting edge which begin with `performed` and have more steps
+    fn gestures_continuing<'a>(&self, config: &'a Config, performed: &[PerformedSequenceStep]) -> impl Iterator<Item = &'a Gesture> {
+        let edge = self.starting_edge;
+        self.active_gestures(config)
+            .into_iter()
+            .filter(move |gesture| {
+                gesture.edge == edge
+                    && gesture.sequence.len() > performed.len()
+                    && gesture.sequence.iter().zip(performed).all(|(defined, performed)| defined == performed)
+            })
+    }
+
+    /// Completes holds and pending taps, see [`GesturesEngine::next_timeout`]
+    pub fn update_timeout(&mut self, now: Duration) {
+        if self.previous_state.positions.is_empty() {
+            if let Some(last_tap) = self.last_tap
+                && now >= last_tap + self.config.load().options.tap_interval
+            {
+                self.flush_taps();
+            }
+            return;
+        }
+
+        let Some(hold_start) = self.hold_start else {
+            return;
+        };
+        // Only holds a gesture is waiting for are steps, other rests would keep the following steps from matching
+        let is_hold = if self.recording {
+            hold_start + RECORDED_HOLD_MIN <= now
+        } else {
+            self.expected_hold_durations(&self.config.load()).iter().any(|duration| hold_start + *duration <= now)
+        };
+        if !is_hold {
+            return;
+        }
+
+        let slots = self.previous_state.positions.keys().cloned().collect::<HashSet<u8>>();
+        if let Some(PerformedSequenceStep::Hold { slots: s, time }) = self.performed_sequence.last_mut() {
+            *s = slots;
+            time.end = now;
+        } else {
+            self.push_step(PerformedSequenceStep::Hold { slots, time: StepTime { start: hold_start, end: now } });
+        }
+
+        // Holds fire while the fingers are still down, so make sure lifting them doesn't match the same sequence again
+        if self.match_gestures(RepeatMode::None) {
+            self.performed_sequence.clear();
+            self.hold_start = None;
+            self.hold_matched = true;
+        }
+    }
+
     pub fn point_outside_of_ellipse(&self, point: &Position, center: &Position, is_edge: bool, config: &Config) -> bool {
         let sensitivity = if is_edge { 1.0 - config.options.edge.sensitivity } else { 1.0 };
         let nx = (point.x as f64 - center.x as f64) / (self.move_threshold_units.x as f64 * sensitivity as f64);
@@ -266,13 +886,28 @@ impl GesturesEngine {
         v > 1.0
     }
 
-    pub fn point_side_in_ellipse(&self, point: &Position, center: &Position) -> Direction {
+    pub fn point_side_in_ellipse(&self, point: &Position, center: &Position, diagonals: bool) -> Direction {
         let dx = point.x as f64 - center.x as f64;
         let dy = point.y as f64 - center.y as f64;
 
         let nx = dx / self.move_threshold_units.x as f64;
         let ny = dy / self.move_threshold_units.y as f64;
 
+        if diagonals {
+            // Split the ellipse into 8 sectors of 45 degrees, each centered on its direction
+            let sector = (ny.atan2(nx) / std::f64::consts::FRAC_PI_4).round() as i8;
+            return match sector.rem_euclid(8) {
+                0 => Direction::Right,
+                1 => Direction::DownRight,
+                2 => Direction::Down,
+                3 => Direction::DownLeft,
+                4 => Direction::Left,
+                5 => Direction::UpLeft,
+                6 => Direction::Up,
+                _ => Direction::UpRight,
+            };
+        }
+
         if nx.abs() > ny.abs() {
             if dx >= 0.0 {
                 Direction::Right
@@ -286,8 +921,57 @@ impl GesturesEngine {
         }
     }
 
+    /// Distance travelled along `direction`, as a fraction of the touchpad size
+    fn normalized_distance(&self, distance: &Position, direction: Direction) -> Option<f32> {
+        let nx = distance.x as f32 / self.touchpad_size.x as f32;
+        let ny = distance.y as f32 / self.touchpad_size.y as f32;
+        match direction {
+            Direction::Up | Direction::Down => Some(ny),
+            Direction::Left | Direction::Right => Some(nx),
+            Direction::UpLeft | Direction::UpRight | Direction::DownLeft | Direction::DownRight => Some((nx + ny) / std::f32::consts::SQRT_2),
+            Direction::None => None,
+        }
+    }
+
+    fn active_gestures<'a>(&self, config: &'a Config) -> Vec<&'a Gesture> {
+        let active_window = self.active_window.active_window();
+
+        let mut app_gestures_by_class = Vec::new();
+        for (regex, gestures) in &config.application_gestures.by_class {
+            if regex.is_match(&active_window.class) {
+                app_gestures_by_class.extend(gestures);
+            }
+        }
+
+        let mut app_gestures_by_title = Vec::new();
+        for (regex, gestures) in &config.application_gestures.by_title {
+            if regex.is_match(&active_window.title) {
+                app_gestures_by_title.extend(gestures);
+            }
+        }
+
+        config.gestures
+            .iter()
+            .chain(app_gestures_by_class)
+            .chain(app_gestures_by_title)
+            .filter(|gesture| gesture.when.matches(&active_window))
+            .collect()
+    }
+
     fn match_gestures(&mut self, repeat_mode: RepeatMode) -> bool {
-        let config = self.config.load();
+        if self.recording {
+            return false;
+        }
+
+        // Continuous gestures follow the fingers until they are lifted or their number changes, even through new steps
+        if !self.continuous_gestures.is_empty() {
+            return false;
+        }
+
+        // What the fingers do after a hold fired belongs to the hold until they are lifted
+        if self.hold_matched {
+            return false;
+        }
 
         // Temporarily remove all trailing touch up and down steps for matching
         let trailing_count = self.performed_sequence.iter()
@@ -304,90 +988,308 @@ impl GesturesEngine {
             }
         }
 
-        let active_window = self.active_window.load();
-
-        let mut app_gestures_by_class = Vec::new();
-        for (regex, gestures) in &config.application_gestures.by_class {
-            if regex.is_match(&active_window.class) {
-                app_gestures_by_class.extend(gestures);
-            }
+        let matched_gestures = self.run_matching_gestures(&self.performed_sequence, self.starting_edge, &repeat_mode);
+        if !matched_gestures.is_empty() {
+            self.begin_continuous_gestures(matched_gestures);
+            self.repeat_mode = repeat_mode;
+            return true;
         }
 
-        let mut app_gestures_by_title = Vec::new();
-        for (regex, gestures) in &config.application_gestures.by_title {
-            if regex.is_match(&active_window.title) {
-                app_gestures_by_title.extend(gestures);
-            }
-        }
+        self.performed_sequence.extend(trailing_steps);
 
-        let app_gestures = app_gestures_by_class
+        false
+    }
+
+    /// Runs the commands of gestures matching `sequence` and returns them
+    fn run_matching_gestures(&self, sequence: &[PerformedSequenceStep], edge: Option<Edge>, repeat_mode: &RepeatMode) -> Vec<Gesture> {
+        let config = self.config.load();
+
+        let mut matching_gestures = self.active_gestures(&config)
             .into_iter()
-            .chain(app_gestures_by_title);
-        let matching_gestures = config.gestures
-            .iter()
-            .chain(app_gestures)
-            .filter(|g| self.does_gesture_match(g, &repeat_mode))
+            .filter(|g| Self::does_gesture_match(g, sequence, edge, repeat_mode))
             .cloned()
             .collect::<Vec<_>>();
 
-        if !matching_gestures.is_empty() {
-            if config.options.run_all_matches {
-                let names = matching_gestures.iter().map(|g| &g.name).collect::<Vec<_>>();
-                log::debug!("Matched gestures: {:?}", names);
+        if matching_gestures.is_empty() {
+            return matching_gestures;
+        }
 
-                for gesture in &matching_gestures {
-                    self.run_command(&gesture.command);
-                }
-            } else {
-                let mut matched_gesture = &matching_gestures[0];
-                let mut distance = 0.0f32;
-
-                for gesture in matching_gestures.iter().skip(1) {
-                    for step in &gesture.sequence {
-                        if let DefinedSequenceStep::Move { distance: dst, .. } = step
-                            && let Some(dst) = dst
-                            && *dst > distance
-                        {
-                            distance = *dst;
-                            matched_gesture = gesture;
-                        }
+        if config.options.run_all_matches {
+            let names = matching_gestures.iter().map(|g| &g.name).collect::<Vec<_>>();
+            log::debug!("Matched gestures: {:?}", names);
+
+            self.count_repeats(&matching_gestures);
+            for gesture in &matching_gestures {
+                self.run_gesture(gesture, sequence, edge, &config);
+            }
+
+            matching_gestures
+        } else {
+            // Flicks are more specific than moves in the same direction with the same fingers, so prefer them
+            let superseded = matching_gestures.iter()
+                .map(|gesture| matching_gestures.iter().any(|other| other.is_flick_of(gesture)))
+                .collect::<Vec<_>>();
+            let mut superseded = superseded.into_iter();
+            matching_gestures.retain(|_| !superseded.next().unwrap_or(false));
+
+            let mut matched_gesture = &matching_gestures[0];
+            let mut distance = 0.0f32;
+
+            for gesture in matching_gestures.iter().skip(1) {
+                for step in &gesture.sequence {
+                    if let DefinedSequenceStep::Move { distance: dst, .. } = step
+                        && let Some(dst) = dst
+                        && *dst > distance
+                    {
+                        distance = *dst;
+                        matched_gesture = gesture;
                     }
                 }
+            }
 
-                log::debug!("Matched gesture: {:?}", matched_gesture.name);
+            log::debug!("Matched gesture: {:?}", matched_gesture.name);
 
-                self.run_command(&matched_gesture.command);
+            self.count_repeats([matched_gesture]);
+            self.run_gesture(matched_gesture, sequence, edge, &config);
+
+            vec![matched_gesture.clone()]
+        }
+    }
+
+    /// Counts a run of each of the gestures, the repeats of other gestures end
+    fn count_repeats<'a>(&self, gestures: impl IntoIterator<Item = &'a Gesture>) {
+        let mut repeat_counts = self.repeat_counts.borrow_mut();
+        *repeat_counts = gestures.into_iter()
+            .map(|gesture| (gesture.name.clone(), repeat_counts.get(&gesture.name).copied().unwrap_or_default() + 1))
+            .collect();
+    }
+
+    fn run_gesture(&self, gesture: &Gesture, sequence: &[PerformedSequenceStep], edge: Option<Edge>, config: &Config) {
+        let mut env = self.gesture_env(gesture, sequence, edge);
+        let value = sequence.last().and_then(|step| step.value()).unwrap_or_default();
+        self.emit(Event::GestureMatched { name: gesture.name.clone() });
+
+        if !gesture.repeat_mode.contains(RepeatMode::Continuous) {
+            env.push(("GEST_DISTANCE", value.to_string()));
+            if let Some(action) = &gesture.action {
+                self.execute(gesture, action, &env);
             }
+            return;
+        }
 
-            self.repeat_mode = repeat_mode;
-            return true;
+        let target = Self::continuous_target(gesture, config);
+        self.run_phase(gesture, &env, Phase::Begin, value, value, target);
+    }
+
+    /// Environment variables describing the gesture and the active window, passed to the gesture's commands
+    fn gesture_env(&self, gesture: &Gesture, sequence: &[PerformedSequenceStep], edge: Option<Edge>) -> Vec<(&'static str, String)> {
+        let active_window = self.active_window.active_window();
+        let last_step = sequence.last();
+
+        vec![
+            ("GEST_NAME", gesture.name.clone()),
+            ("GEST_FINGERS", last_step.map_or(0, |step| step.slots().len()).to_string()),
+            ("GEST_DIRECTION", last_step.and_then(|step| step.direction_name()).unwrap_or_default().to_string()),
+            ("GEST_EDGE", edge.map_or("", |edge| edge.as_str()).to_string()),
+            ("GEST_WINDOW_CLASS", active_window.class.clone()),
+            ("GEST_WINDOW_TITLE", active_window.title.clone()),
+            ("GEST_REPEAT_COUNT", self.repeat_counts.borrow().get(&gesture.name).copied().unwrap_or_default().to_string()),
+        ]
+    }
+
+    fn continuous_target(gesture: &Gesture, config: &Config) -> f32 {
+        gesture.sequence.last()
+            .and_then(|step| step.target_value(&config.options))
+            .filter(|target| *target > 0.0)
+            .unwrap_or(1.0)
+    }
+
+    fn run_phase(&self, gesture: &Gesture, env: &[(&'static str, String)], phase: Phase, value: f32, delta: f32, target: f32) {
+        let phase_command = match phase {
+            Phase::Begin => gesture.begin_command.as_ref(),
+            Phase::Update => gesture.update_command.as_ref(),
+            Phase::End => gesture.end_command.as_ref(),
+        };
+        let Some(action) = phase_command.map(|command| GestureAction::Command(command.clone())).or_else(|| gesture.action.clone()) else {
+            return;
+        };
+
+        log::debug!("Gesture {:?} {} (distance: {}, delta: {})", gesture.name, phase.as_str(), value, delta);
+
+        let mut env = env.to_vec();
+        env.extend([
+            ("GEST_PHASE", phase.as_str().to_string()),
+            ("GEST_DISTANCE", value.to_string()),
+            ("GEST_DELTA", delta.to_string()),
+            ("GEST_PROGRESS", (value / target).to_string()),
+        ]);
+        self.execute(gesture, &action, &env);
+    }
+
+    fn begin_continuous_gestures(&mut self, gestures: Vec<Gesture>) {
+        let config = self.config.load();
+        let last_step = self.performed_sequence.last();
+        let value = last_step.and_then(|step| step.value()).unwrap_or_default();
+        for gesture in gestures {
+            if !gesture.repeat_mode.contains(RepeatMode::Continuous) {
+                continue;
+            }
+
+            let tracking = match last_step {
+                Some(PerformedSequenceStep::Move { direction, .. }) => match self.sequence_step_start_state.centroid() {
+                    Some(origin) => Tracking::Move { origin, direction: *direction },
+                    None => continue,
+                },
+                Some(PerformedSequenceStep::Pinch { direction, .. }) => match self.pinch_start_spread {
+                    Some(start_spread) => Tracking::Pinch { start_spread, direction: *direction },
+                    None => continue,
+                },
+                Some(PerformedSequenceStep::Rotate { direction, angle, .. }) => Tracking::Rotate { angle: *angle, direction: *direction },
+                _ => Tracking::Hold,
+            };
+            let target = Self::continuous_target(&gesture, &config);
+            let env = self.gesture_env(&gesture, &self.performed_sequence, self.starting_edge);
+            self.continuous_gestures.push(ContinuousGesture { gesture, env, tracking, target, value });
         }
+    }
 
-        self.performed_sequence.extend(trailing_steps);
+    /// Signed value of a continuous gesture's last step in `state`, none if it can't be measured
+    fn tracked_value(&self, tracking: &mut Tracking, state: &State) -> Option<f32> {
+        match tracking {
+            Tracking::Move { origin, direction } => {
+                let centroid = state.centroid()?;
+                let dx = (centroid.x as f32 - origin.x as f32) / self.touchpad_size.x as f32;
+                let dy = (centroid.y as f32 - origin.y as f32) / self.touchpad_size.y as f32;
+                let diagonal = |x: f32, y: f32| (x + y) / std::f32::consts::SQRT_2;
+                Some(match direction {
+                    Direction::Right => dx,
+                    Direction::Left => -dx,
+                    Direction::Down => dy,
+                    Direction::Up => -dy,
+                    Direction::UpLeft => diagonal(-dx, -dy),
+                    Direction::UpRight => diagonal(dx, -dy),
+                    Direction::DownLeft => diagonal(-dx, dy),
+                    Direction::DownRight => diagonal(dx, dy),
+                    Direction::None => return None,
+                })
+            }
+            Tracking::Pinch { start_spread, direction } => {
+                let scale = state.spread()? / *start_spread;
+                Some(match direction {
+                    PinchDirection::In => 1.0 - scale,
+                    PinchDirection::Out => scale - 1.0,
+                })
+            }
+            Tracking::Rotate { angle, direction } => {
+                let delta = state.rotation_since(&self.previous_state)?;
+                *angle += match direction {
+                    RotationDirection::Clockwise => delta,
+                    RotationDirection::CounterClockwise => -delta,
+                };
+                Some(*angle)
+            }
+            Tracking::Hold => match self.performed_sequence.last() {
+                Some(step @ PerformedSequenceStep::Hold { .. }) => step.value(),
+                _ => None,
+            },
+        }
+    }
 
-        false
+    fn update_continuous_gestures(&mut self, state: &State, config: &Config) {
+        if self.continuous_gestures.is_empty() {
+            return;
+        }
+
+        let mut continuous_gestures = std::mem::take(&mut self.continuous_gestures);
+        for cg in &mut continuous_gestures {
+            let Some(value) = self.tracked_value(&mut cg.tracking, state) else {
+                continue;
+            };
+
+            let delta = value - cg.value;
+            if (delta / cg.target).abs() >= config.options.continuous_update_threshold {
+                self.run_phase(&cg.gesture, &cg.env, Phase::Update, value, delta, cg.target);
+                cg.value = value;
+            }
+        }
+        self.continuous_gestures = continuous_gestures;
     }
 
-    fn does_gesture_match(&self, gesture: &Gesture, repeat_mode: &RepeatMode) -> bool {
-        if gesture.sequence.len() != self.performed_sequence.len()
-            || !gesture.repeat_mode.contains(RepeatMode::Slide) && *repeat_mode == RepeatMode::Slide
-            || gesture.edge != self.starting_edge
+    fn end_continuous_gestures(&mut self) {
+        for cg in std::mem::take(&mut self.continuous_gestures) {
+            self.run_phase(&cg.gesture, &cg.env, Phase::End, cg.value, 0.0, cg.target);
+        }
+    }
+
+    fn does_gesture_match(gesture: &Gesture, sequence: &[PerformedSequenceStep], edge: Option<Edge>, repeat_mode: &RepeatMode) -> bool {
+        if gesture.sequence.len() != sequence.len()
+            || !gesture.repeat_mode.intersects(RepeatMode::Slide | RepeatMode::Continuous) && *repeat_mode == RepeatMode::Slide
+            || gesture.edge != edge
         {
             return false;
         }
 
-        gesture.sequence == self.performed_sequence
+        if let Some(timeout) = gesture.timeout
+            && sequence.windows(2).any(|steps| steps[1].time().start.saturating_sub(steps[0].time().end) > timeout)
+        {
+            return false;
+        }
+
+        gesture.sequence == sequence
     }
 
-    fn run_command(&self, command: &str) {
-        if let Err(e) = std::process::Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-        {
-            log::error!("Failed to execute command '{}': {}", command, e);
+    fn register_tap(&mut self, now: Duration) {
+        if self.tap_sequence.is_empty() {
+            self.tap_edge = self.starting_edge;
+        }
+        self.tap_sequence.push(PerformedSequenceStep::TouchDown { slots: self.tap_slots.clone(), time: StepTime::at(self.touch_down_time) });
+        self.tap_sequence.push(PerformedSequenceStep::TouchUp { slots: self.tap_slots.clone(), time: StepTime::at(now) });
+        self.last_tap = Some(now);
+        self.previous_tap = Some(now);
+
+        // Only wait for the next tap if it could still complete a longer tap gesture
+        if !self.expects_more_taps() {
+            self.flush_taps();
+        }
+    }
+
+    fn expects_more_taps(&self) -> bool {
+        let config = self.config.load();
+        self.active_gestures(&config).iter().any(|gesture| {
+            gesture.edge == self.tap_edge
+                && gesture.sequence.len() > self.tap_sequence.len()
+                && gesture.sequence.iter().all(|step| matches!(step, DefinedSequenceStep::TouchDown { .. } | DefinedSequenceStep::TouchUp { .. }))
+                && gesture.sequence.iter().zip(&self.tap_sequence).all(|(defined, performed)| defined == performed)
+        })
+    }
+
+    fn flush_taps(&mut self) {
+        if self.tap_sequence.is_empty() {
+            return;
+        }
+
+        if let Some(edge) = self.tap_edge {
+            log::debug!("Performed taps from edge {:?}: {:?}", edge, self.tap_sequence);
+        } else {
+            log::debug!("Performed taps: {:?}", self.tap_sequence);
+        }
+
+        self.run_matching_gestures(&self.tap_sequence, self.tap_edge, &RepeatMode::None);
+        self.tap_sequence.clear();
+        self.tap_edge = None;
+        self.last_tap = None;
+    }
+
+    fn execute(&self, gesture: &Gesture, action: &GestureAction, env: &[(&'static str, String)]) {
+        let event = match action {
+            GestureAction::Keys(keys) => Event::KeysPressed { gesture: gesture.name.clone(), keys: keys.to_string() },
+            GestureAction::DBus(call) => Event::DbusCalled { gesture: gesture.name.clone(), call: call.to_string() },
+            _ => Event::CommandExecuted { gesture: gesture.name.clone(), command: action.to_string() },
+        };
+        self.emit(event);
+
+        if let Err(e) = self.executor.execute(action, env) {
+            log::error!("Failed to run '{}' of gesture {:?}: {}", action, gesture.name, e);
         }
     }
 }
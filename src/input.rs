@@ -1,5 +1,8 @@
-use evdev::{AbsoluteAxisCode, Device, EventType, KeyCode};
-use crate::gestures::MoveThresholdUnits;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+use evdev::{AbsoluteAxisCode, Device, EventStream, EventType, InputEvent, KeyCode};
+use crate::gestures::{MoveThresholdUnits, Position, State};
 
 pub fn get_touchpad_device() -> Option<Device> {
     for (_, device) in evdev::enumerate() {
@@ -34,3 +37,118 @@ pub fn get_touchpad_size(touchpad_device: &Device) -> Result<MoveThresholdUnits,
 
     Ok(MoveThresholdUnits { x: width, y: height })
 }
+
+/// Converts a timestamp to the time base of `State::time`, evdev timestamps use the realtime clock by default
+pub fn timestamp(time: SystemTime) -> Duration {
+    time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Touchpad event as read from the device or a trace file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawEvent {
+    /// time in the time base of `State::time`
+    pub time: Duration,
+    pub event_type: EventType,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl From<&InputEvent> for RawEvent {
+    fn from(event: &InputEvent) -> Self {
+        RawEvent { time: timestamp(event.timestamp()), event_type: event.event_type(), code: event.code(), value: event.value() }
+    }
+}
+
+impl RawEvent {
+    /// Whether the event is needed to track touches, other events are not recorded in traces
+    pub fn is_multitouch(&self) -> bool {
+        match self.event_type {
+            EventType::ABSOLUTE => self.code >= AbsoluteAxisCode::ABS_MT_SLOT.0 && self.code <= AbsoluteAxisCode::ABS_MT_TOOL_Y.0,
+            EventType::SYNCHRONIZATION => true,
+            _ => false,
+        }
+    }
+}
+
+/// Tracks the positions of multitouch slots, producing the touchpad state on each synchronization event
+#[derive(Debug, Default)]
+pub struct SlotTracker {
+    slots: HashMap<u8, (Option<u16>, Option<u16>)>,
+    current_slot: u8,
+}
+
+impl SlotTracker {
+    pub fn handle_event(&mut self, event: &RawEvent) -> Option<State> {
+        match event.event_type {
+            EventType::ABSOLUTE => {
+                match AbsoluteAxisCode(event.code) {
+                    AbsoluteAxisCode::ABS_MT_SLOT => {
+                        self.current_slot = event.value as u8;
+                    }
+                    AbsoluteAxisCode::ABS_MT_TRACKING_ID => {
+                        if event.value == -1 {
+                            self.slots.remove(&self.current_slot);
+                        } else {
+                            self.slots.insert(self.current_slot, (None, None));
+                        }
+                    }
+                    AbsoluteAxisCode::ABS_MT_POSITION_X => {
+                        if let Some(position) = self.slots.get_mut(&self.current_slot) {
+                            position.0 = Some(event.value as u16);
+                        }
+                    }
+                    AbsoluteAxisCode::ABS_MT_POSITION_Y => {
+                        if let Some(position) = self.slots.get_mut(&self.current_slot) {
+                            position.1 = Some(event.value as u16);
+                        }
+                    }
+                    _ => {}
+                }
+                None
+            },
+            EventType::SYNCHRONIZATION => {
+                let mut filtered_state = State { time: event.time, ..Default::default() };
+                for (u8, (pos_x, pos_y)) in &self.slots {
+                    if let (Some(x), Some(y)) = (pos_x, pos_y) {
+                        filtered_state.positions.insert(*u8, Position { x: *x, y: *y });
+                    }
+                }
+                Some(filtered_state)
+            },
+            _ => None,
+        }
+    }
+}
+
+/// A source of raw touchpad events
+pub trait InputSource {
+    /// Size of the touchpad in device units
+    fn touchpad_size(&self) -> MoveThresholdUnits;
+
+    /// Waits for the next event, `None` once the source has no more events
+    fn next_event(&mut self) -> impl Future<Output = std::io::Result<Option<RawEvent>>> + Send;
+}
+
+/// The first touchpad device found
+pub struct Touchpad {
+    events: EventStream,
+    size: MoveThresholdUnits,
+}
+
+impl Touchpad {
+    pub fn open() -> Result<Self, Box<dyn std::error::Error>> {
+        let device = get_touchpad_device().ok_or("No touchpad device found.")?;
+        let size = get_touchpad_size(&device).map_err(|e| format!("Could not determine touchpad size: {}", e))?;
+        Ok(Touchpad { events: device.into_event_stream()?, size })
+    }
+}
+
+impl InputSource for Touchpad {
+    fn touchpad_size(&self) -> MoveThresholdUnits {
+        self.size
+    }
+
+    async fn next_event(&mut self) -> std::io::Result<Option<RawEvent>> {
+        self.events.next_event().await.map(|event| Some(RawEvent::from(&event)))
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use evdev::uinput::VirtualDevice;
+use evdev::{AttributeSet, KeyCode, KeyEvent};
+
+/// How long the compositor takes to pick up a new device, keys sent before then are lost
+const DEVICE_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Keys pressed together, e.g. `ctrl+shift+tab`
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyChord(Vec<KeyCode>);
+
+impl KeyChord {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut keys = Vec::new();
+        for name in s.split('+') {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(format!("Invalid key chord: '{}'", s));
+            }
+            keys.push(parse_key(name).ok_or_else(|| format!("Unknown key: '{}'", name))?);
+        }
+        Ok(KeyChord(keys))
+    }
+}
+
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self.0.iter().map(|key| format!("{:?}", key)).collect();
+        write!(f, "{}", names.join("+"))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for KeyChord {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        KeyChord::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parses a human-readable key name like `ctrl`, `tab` or `f5`, falling back to evdev names like `KEY_PLAYPAUSE`
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let lower = name.to_lowercase();
+    let key = match lower.as_str() {
+        "ctrl" | "control" | "leftctrl" => KeyCode::KEY_LEFTCTRL,
+        "rightctrl" => KeyCode::KEY_RIGHTCTRL,
+        "shift" | "leftshift" => KeyCode::KEY_LEFTSHIFT,
+        "rightshift" => KeyCode::KEY_RIGHTSHIFT,
+        "alt" | "leftalt" => KeyCode::KEY_LEFTALT,
+        "altgr" | "rightalt" => KeyCode::KEY_RIGHTALT,
+        "super" | "meta" | "win" | "logo" | "leftmeta" => KeyCode::KEY_LEFTMETA,
+        "rightmeta" => KeyCode::KEY_RIGHTMETA,
+        "esc" | "escape" => KeyCode::KEY_ESC,
+        "tab" => KeyCode::KEY_TAB,
+        "enter" | "return" => KeyCode::KEY_ENTER,
+        "space" => KeyCode::KEY_SPACE,
+        "backspace" => KeyCode::KEY_BACKSPACE,
+        "delete" | "del" => KeyCode::KEY_DELETE,
+        "insert" | "ins" => KeyCode::KEY_INSERT,
+        "home" => KeyCode::KEY_HOME,
+        "end" => KeyCode::KEY_END,
+        "pageup" | "pgup" => KeyCode::KEY_PAGEUP,
+        "pagedown" | "pgdn" => KeyCode::KEY_PAGEDOWN,
+        "up" => KeyCode::KEY_UP,
+        "down" => KeyCode::KEY_DOWN,
+        "left" => KeyCode::KEY_LEFT,
+        "right" => KeyCode::KEY_RIGHT,
+        "capslock" => KeyCode::KEY_CAPSLOCK,
+        "print" | "printscreen" => KeyCode::KEY_SYSRQ,
+        "minus" | "-" => KeyCode::KEY_MINUS,
+        "equal" | "=" => KeyCode::KEY_EQUAL,
+        "leftbrace" | "[" => KeyCode::KEY_LEFTBRACE,
+        "rightbrace" | "]" => KeyCode::KEY_RIGHTBRACE,
+        "semicolon" | ";" => KeyCode::KEY_SEMICOLON,
+        "apostrophe" | "'" => KeyCode::KEY_APOSTROPHE,
+        "grave" | "`" => KeyCode::KEY_GRAVE,
+        "backslash" | "\\" => KeyCode::KEY_BACKSLASH,
+        "comma" | "," => KeyCode::KEY_COMMA,
+        "dot" | "period" | "." => KeyCode::KEY_DOT,
+        "slash" | "/" => KeyCode::KEY_SLASH,
+        "volumeup" => KeyCode::KEY_VOLUMEUP,
+        "volumedown" => KeyCode::KEY_VOLUMEDOWN,
+        "mute" => KeyCode::KEY_MUTE,
+        "playpause" => KeyCode::KEY_PLAYPAUSE,
+        "next" | "nextsong" => KeyCode::KEY_NEXTSONG,
+        "previous" | "previoussong" => KeyCode::KEY_PREVIOUSSONG,
+        "brightnessup" => KeyCode::KEY_BRIGHTNESSUP,
+        "brightnessdown" => KeyCode::KEY_BRIGHTNESSDOWN,
+        _ => {
+            // Letters, digits and function keys map directly to their evdev names
+            let is_simple = lower.len() == 1 && lower.chars().all(|c| c.is_ascii_alphanumeric());
+            let is_function = lower.strip_prefix('f').is_some_and(|n| n.parse::<u8>().is_ok_and(|n| (1..=24).contains(&n)));
+            if is_simple || is_function {
+                return format!("KEY_{}", lower.to_uppercase()).parse().ok();
+            }
+            return name.to_uppercase().parse().ok();
+        }
+    };
+    Some(key)
+}
+
+/// Virtual keyboard injecting key chords through uinput, the device is created by [`Keyboard::open`] or on first use
+#[derive(Debug, Default, Clone)]
+pub struct Keyboard {
+    /// the device and when it can be used
+    device: Arc<Mutex<Option<(VirtualDevice, Instant)>>>,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the device on a separate thread, so that the first key press doesn't wait for it to be created
+    pub fn open(&self) {
+        let device = self.device.clone();
+        std::thread::spawn(move || {
+            let mut guard = device.lock().unwrap_or_else(|e| e.into_inner());
+            if guard.is_none() {
+                match Self::create_device() {
+                    Ok(created) => *guard = Some(created),
+                    Err(e) => log::error!("Failed to create virtual keyboard: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Presses the keys of the chord in order and releases them in reverse order.
+    /// Modifiers are reported before the last key, so that clients see them held when it is pressed.
+    pub fn press(&self, chord: &KeyChord) -> Result<(), Box<dyn std::error::Error>> {
+        let mut guard = self.device.lock().unwrap_or_else(|e| e.into_inner());
+        let (device, ready_at) = match &mut *guard {
+            Some(opened) => opened,
+            empty => empty.insert(Self::create_device()?),
+        };
+        let settle_time = ready_at.saturating_duration_since(Instant::now());
+        if !settle_time.is_zero() {
+            std::thread::sleep(settle_time);
+        }
+
+        let Some((key, modifiers)) = chord.0.split_last() else {
+            return Ok(());
+        };
+        let modifiers_press: Vec<_> = modifiers.iter().map(|key| *KeyEvent::new(*key, 1)).collect();
+        let modifiers_release: Vec<_> = modifiers.iter().rev().map(|key| *KeyEvent::new(*key, 0)).collect();
+        // Every emit ends with its own SYN_REPORT
+        if !modifiers_press.is_empty() {
+            device.emit(&modifiers_press)?;
+        }
+        device.emit(&[*KeyEvent::new(*key, 1)])?;
+        device.emit(&[*KeyEvent::new(*key, 0)])?;
+        if !modifiers_release.is_empty() {
+            device.emit(&modifiers_release)?;
+        }
+        Ok(())
+    }
+
+    fn create_device() -> Result<(VirtualDevice, Instant), Box<dyn std::error::Error>> {
+        let mut keys = AttributeSet::<KeyCode>::new();
+        for code in KeyCode::KEY_ESC.code()..KeyCode::BTN_0.code() {
+            keys.insert(KeyCode::new(code));
+        }
+
+        let device = VirtualDevice::builder()?
+            .name("gest virtual keyboard")
+            .with_keys(&keys)?
+            .build()?;
+
+        log::info!("Created virtual keyboard");
+        Ok((device, Instant::now() + DEVICE_SETTLE_TIME))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases() {
+        assert_eq!(parse_key("ctrl"), Some(KeyCode::KEY_LEFTCTRL));
+        assert_eq!(parse_key("Control"), Some(KeyCode::KEY_LEFTCTRL));
+        assert_eq!(parse_key("super"), Some(KeyCode::KEY_LEFTMETA));
+        assert_eq!(parse_key("altgr"), Some(KeyCode::KEY_RIGHTALT));
+        assert_eq!(parse_key("esc"), Some(KeyCode::KEY_ESC));
+        assert_eq!(parse_key("pgdn"), Some(KeyCode::KEY_PAGEDOWN));
+        assert_eq!(parse_key(","), Some(KeyCode::KEY_COMMA));
+        assert_eq!(parse_key("next"), Some(KeyCode::KEY_NEXTSONG));
+    }
+
+    #[test]
+    fn letters_digits_and_function_keys() {
+        assert_eq!(parse_key("a"), Some(KeyCode::KEY_A));
+        assert_eq!(parse_key("T"), Some(KeyCode::KEY_T));
+        assert_eq!(parse_key("5"), Some(KeyCode::KEY_5));
+        assert_eq!(parse_key("f1"), Some(KeyCode::KEY_F1));
+        assert_eq!(parse_key("F24"), Some(KeyCode::KEY_F24));
+        assert_eq!(parse_key("f25"), None);
+    }
+
+    #[test]
+    fn evdev_names() {
+        assert_eq!(parse_key("KEY_ZOOMIN"), Some(KeyCode::KEY_ZOOMIN));
+        assert_eq!(parse_key("key_playpause"), Some(KeyCode::KEY_PLAYPAUSE));
+    }
+
+    #[test]
+    fn chord_keeps_the_order_of_its_keys() {
+        let chord = KeyChord::parse("ctrl+shift+tab").unwrap();
+        assert_eq!(chord.0, [KeyCode::KEY_LEFTCTRL, KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_TAB]);
+        let chord = KeyChord::parse("shift + ctrl + t").unwrap();
+        assert_eq!(chord.0, [KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_LEFTCTRL, KeyCode::KEY_T]);
+        assert_eq!(chord.to_string(), "KEY_LEFTSHIFT+KEY_LEFTCTRL+KEY_T");
+    }
+
+    #[test]
+    fn unknown_keys() {
+        assert_eq!(parse_key("hyper"), None);
+        assert_eq!(KeyChord::parse("ctrl+hyper"), Err("Unknown key: 'hyper'".to_string()));
+        assert_eq!(KeyChord::parse("ctrl+"), Err("Invalid key chord: 'ctrl+'".to_string()));
+        assert_eq!(KeyChord::parse(""), Err("Invalid key chord: ''".to_string()));
+    }
+}
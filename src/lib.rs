@@ -0,0 +1,65 @@
+//! Touchpad gesture recognition.
+//!
+//! [`GesturesEngine`] turns touchpad [`State`]s into [`PerformedSequenceStep`]s and runs the gestures of a [`Config`]
+//! whose sequence matches. The engine is fed from an [`InputSource`] (usually the [`Touchpad`]) through a [`SlotTracker`],
+//! asks a [`WindowContext`] for the active window, which a [`window_monitor::WindowSource`] keeps up to date,
+//! and runs actions with an [`ActionExecutor`].
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use arc_swap::ArcSwap;
+//! use gest::{Config, GesturesEngine, InputSource, SlotTracker, Touchpad, Window};
+//! use gest::input::calculate_move_threshold_units;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = Arc::new(ArcSwap::from_pointee(Config::parse_from_file("gestures.yaml")?));
+//! let mut touchpad = Touchpad::open()?;
+//! let touchpad_size = touchpad.touchpad_size();
+//! let move_threshold_units = calculate_move_threshold_units(&touchpad_size, config.load().options.move_threshold);
+//! let (events, _) = tokio::sync::broadcast::channel(gest::events::EVENT_BUFFER_SIZE);
+//! let active_window = Arc::new(ArcSwap::from_pointee(Window::default()));
+//! let mut engine = GesturesEngine::new(config, active_window, move_threshold_units, touchpad_size, events);
+//!
+//! let mut slot_tracker = SlotTracker::default();
+//! while let Some(event) = touchpad.next_event().await? {
+//!     if let Some(state) = slot_tracker.handle_event(&event) {
+//!         engine.update_state(state);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod config;
+pub mod control;
+pub mod dbus;
+pub mod events;
+pub mod executor;
+pub mod gestures;
+pub mod input;
+pub mod keys;
+pub mod record;
+pub mod sequence_step;
+pub mod simulator;
+pub mod trace;
+pub mod window_monitor;
+
+pub use config::Config;
+pub use executor::{ActionExecutor, GestureAction};
+pub use gestures::{GesturesEngine, Position, State};
+pub use input::{InputSource, RawEvent, SlotTracker, Touchpad};
+pub use sequence_step::PerformedSequenceStep;
+pub use window_monitor::{WindowContext, WindowSource};
+
+/// The active window and its state
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct Window {
+    pub class: String,
+    pub title: String,
+    /// name of the workspace the window is on, if the window monitor knows it
+    pub workspace: Option<String>,
+    pub fullscreen: bool,
+    pub maximized: bool,
+    pub minimized: bool,
+    pub floating: bool,
+}
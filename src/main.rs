@@ -1,31 +1,25 @@
-mod gestures;
-mod input;
-mod config;
-mod window_monitor;
 mod args;
-mod sequence_step;
 mod lockfile;
 
-use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::SystemTime;
 use arc_swap::ArcSwap;
-use evdev::{AbsoluteAxisCode, EventType};
 use clap::Parser;
 use notify::event::ModifyKind;
 use notify::{EventKind, Watcher};
 use std::path::Path;
-use crate::config::Config;
-use crate::gestures::{GesturesEngine, Position, State};
-use crate::input::{calculate_move_threshold_units, get_touchpad_device, get_touchpad_size};
-use crate::args::Args;
+use tokio::signal::unix::{signal, SignalKind};
+use gest::{control, record, simulator, window_monitor, Config, GesturesEngine, InputSource, SlotTracker, Touchpad, Window};
+use gest::control::{ControlSocket, Request, Response};
+use gest::events::{Event, EVENT_BUFFER_SIZE};
+use gest::executor::{ActionExecutor, SystemExecutor};
+use gest::input::{calculate_move_threshold_units, timestamp};
+use gest::simulator::{Simulator, SIMULATED_TOUCHPAD_SIZE};
+use gest::trace::{Trace, TraceWriter};
+use gest::window_monitor::{OnWindowChange, WindowMonitorKind};
+use crate::args::{Args, Command};
 use crate::lockfile::Lock;
 
-#[derive(Debug, Default)]
-pub struct Window {
-    class: String,
-    title: String,
-}
-
 fn init_logger(args: &Args) {
     let level_filter = match args.verbose {
         0 => log::LevelFilter::Error,
@@ -53,34 +47,212 @@ fn init_logger(args: &Args) {
         .init();
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+fn reload_config(config: &ArcSwap<Config>, config_path: &Path, executor: &dyn ActionExecutor) -> Result<(), Box<dyn std::error::Error>> {
+    let new_config = Config::parse_from_file(config_path)?;
+    executor.prepare(&new_config);
+    config.swap(new_config.into());
+    log::info!("Config reloaded successfully.");
+    Ok(())
+}
 
-    init_logger(&args);
+fn handle_control_request(request: Request, gestures_manager: &mut GesturesEngine, executor: &dyn ActionExecutor, config: &ArcSwap<Config>, config_path: &Path, active_window: &ArcSwap<Window>) -> Response {
+    match request {
+        Request::Status => {
+            let config = config.load();
+            let application_gestures = config.application_gestures.by_class.iter()
+                .chain(&config.application_gestures.by_title)
+                .map(|(_, gestures)| gestures.len())
+                .sum::<usize>();
+            Ok(format!(
+                "state: {}\nconfig: {}\ngestures: {}\napplication gestures: {}\nactive window: {}\n",
+                if gestures_manager.is_paused() { "paused" } else { "running" },
+                config_path.display(),
+                config.gestures.len(),
+                application_gestures,
+                active_window.load().class,
+            ))
+        }
+        Request::Reload => {
+            log::info!("Reload requested, reloading...");
+            reload_config(config, config_path, executor).map_err(|e| format!("Failed to reload config file: {}", e))?;
+            Ok("reloaded\n".to_string())
+        }
+        Request::Pause => {
+            gestures_manager.pause();
+            log::info!("Gestures paused");
+            Ok("paused\n".to_string())
+        }
+        Request::Resume => {
+            gestures_manager.resume();
+            log::info!("Gestures resumed");
+            Ok("running\n".to_string())
+        }
+        Request::ListGestures => {
+            let config = config.load();
+            let mut response = String::new();
+            for gesture in &config.gestures {
+                response.push_str(&format!("{}\n", gesture.name));
+            }
+            for (kind, patterns) in [("class", &config.application_gestures.by_class), ("title", &config.application_gestures.by_title)] {
+                for (regex, gestures) in patterns {
+                    for gesture in gestures {
+                        response.push_str(&format!("[{} {}] {}\n", kind, regex, gesture.name));
+                    }
+                }
+            }
+            Ok(response)
+        }
+        Request::ActiveWindow => {
+            let window = active_window.load();
+            Ok(format!(
+                "class: {}\ntitle: {}\nworkspace: {}\nfullscreen: {}\nmaximized: {}\nminimized: {}\nfloating: {}\n",
+                window.class,
+                window.title,
+                window.workspace.as_deref().unwrap_or_default(),
+                window.fullscreen,
+                window.maximized,
+                window.minimized,
+                window.floating,
+            ))
+        }
+        // Subscriptions are served by the control socket itself
+        Request::Subscribe => Err("Unexpected subscribe request".to_string()),
+        Request::Trigger(name) => {
+            if gestures_manager.trigger(&name) {
+                Ok(format!("triggered {}\n", name))
+            } else {
+                Err(format!("No active gesture named '{}'", name))
+            }
+        }
+    }
+}
 
-    const LOCK_FILE_PATH: &str = "/tmp/gest.lock";
-    let _lockfile = match Lock::acquire(LOCK_FILE_PATH) {
-        Ok(file) => file,
-        Err(_) => {
-            log::error!("Another instance of the application is already running.");
+fn open_touchpad() -> Touchpad {
+    match Touchpad::open() {
+        Ok(touchpad) => touchpad,
+        Err(e) => {
+            log::error!("{}", e);
             std::process::exit(1);
         }
-    };
+    }
+}
 
-    let active_window = Arc::new(ArcSwap::new(Window::default().into()));
+/// Prints every performed gesture as YAML and optionally appends it to a config file, gestures are not run
+async fn record(config: Arc<ArcSwap<Config>>, name: Option<String>, append: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut touchpad = open_touchpad();
+    let touchpad_size = touchpad.touchpad_size();
+    let move_threshold_units = calculate_move_threshold_units(&touchpad_size, config.load().options.move_threshold);
+    let (events, _) = tokio::sync::broadcast::channel(EVENT_BUFFER_SIZE);
+    let mut gestures_manager = GesturesEngine::new(config, Arc::new(ArcSwap::new(Window::default().into())), move_threshold_units, touchpad_size, events);
+    gestures_manager.start_recording();
 
-    std::thread::spawn({
-        let active_window = active_window.clone();
-        move || {
-            let mut wlroots = window_monitor::WlrootsMonitor::new(Box::new(move |class: String, title: String| {
-                let new_window = Window { class, title };
-                log::debug!("Active window changed: {:?}", new_window);
-                active_window.swap(new_window.into());
-            }));
-            wlroots.run();
+    eprintln!("Perform gestures on the touchpad, press Ctrl+C to stop.");
+    let mut count = 0;
+    let mut slot_tracker = SlotTracker::default();
+    loop {
+        let timeout = gestures_manager.next_timeout();
+        tokio::select! {
+            event = touchpad.next_event() => {
+                let Some(event) = event? else {
+                    return Ok(());
+                };
+                if let Some(state) = slot_tracker.handle_event(&event) {
+                    gestures_manager.update_state(state);
+                }
+            }
+            _ = tokio::time::sleep(timeout.unwrap_or_default().saturating_sub(timestamp(SystemTime::now()))), if timeout.is_some() => {
+                gestures_manager.update_timeout(timestamp(SystemTime::now()));
+            }
         }
-    });
+
+        for gesture in gestures_manager.take_recorded() {
+            count += 1;
+            let gesture_name = match &name {
+                Some(name) if count == 1 => name.clone(),
+                Some(name) => format!("{} {}", name, count),
+                None => format!("Gesture {}", count),
+            };
+            let yaml = record::gesture_yaml(&gesture_name, &gesture);
+            println!("{}", yaml);
+
+            if let Some(path) = &append {
+                record::append_to_config(Path::new(path), &yaml)?;
+                eprintln!("Appended {} to {}", gesture_name, path);
+            }
+        }
+    }
+}
+
+/// Runs the events of a trace through the gestures engine, timeouts fire at the trace's time instead of the clock's
+fn replay(config: Arc<ArcSwap<Config>>, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let trace = Trace::read(path).map_err(|e| format!("Failed to read trace {}: {}", path.display(), e))?;
+    let executor = Arc::new(SystemExecutor::default());
+    executor.prepare(&config.load());
+    let mut simulator = Simulator::new(config, trace.touchpad_size).with_executor(executor);
+
+    let mut slot_tracker = SlotTracker::default();
+    for event in &trace.events {
+        if let Some(state) = slot_tracker.handle_event(event) {
+            simulator.feed(state);
+        }
+        print_events(simulator.take_events());
+    }
+    // Pending taps and holds complete after the last event
+    simulator.finish();
+    print_events(simulator.take_events());
+
+    Ok(())
+}
+
+fn print_events(events: Vec<Event>) {
+    for event in events {
+        if let Ok(line) = serde_json::to_string(&event) {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Runs simulation scripts and reports their failed expectations, returns whether all of them passed
+fn simulate(config: Arc<ArcSwap<Config>>, scripts: &[String]) -> bool {
+    let mut passed = true;
+    for path in scripts {
+        let steps = match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|script| simulator::parse_script(&script)) {
+            Ok(steps) => steps,
+            Err(e) => {
+                println!("{}: invalid script: {}", path, e);
+                passed = false;
+                continue;
+            }
+        };
+
+        let mut simulator = Simulator::new(config.clone(), SIMULATED_TOUCHPAD_SIZE);
+        let failures = simulator::run_script(&mut simulator, &steps);
+        if failures.is_empty() {
+            println!("{}: ok", path);
+        } else {
+            println!("{}: FAILED", path);
+            for failure in failures {
+                println!("  {}", failure);
+            }
+            passed = false;
+        }
+    }
+    passed
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    if let Some(Command::Ctl { command }) = &args.command {
+        if let Err(e) = control::send_request(&command.join(" "), &mut std::io::stdout()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    init_logger(&args);
 
     let config_path = if let Some(config_file) = &args.config_file {
         Path::new(&config_file).to_path_buf()
@@ -106,9 +278,71 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     log::debug!("Loaded config: {:#?}", config);
 
+    if let Some(Command::Record { name, append }) = args.command {
+        return record(config, name, append).await;
+    }
+
+    if let Some(Command::Simulate { scripts }) = &args.command {
+        if !simulate(config, scripts) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(trace_path) = &args.replay {
+        return replay(config, Path::new(trace_path));
+    }
+
+    const LOCK_FILE_PATH: &str = "/tmp/gest.lock";
+    let _lockfile = match Lock::acquire(LOCK_FILE_PATH) {
+        Ok(file) => file,
+        Err(_) => {
+            log::error!("Another instance of the application is already running.");
+            std::process::exit(1);
+        }
+    };
+
+    let active_window = Arc::new(ArcSwap::new(Window::default().into()));
+    let (events, _) = tokio::sync::broadcast::channel(EVENT_BUFFER_SIZE);
+
+    let mut window_monitors = config.load().options.window_monitor.clone();
+    if window_monitors.is_empty() {
+        window_monitors = WindowMonitorKind::default_order();
+    }
+
+    std::thread::spawn({
+        let active_window = active_window.clone();
+        let events = events.clone();
+        move || {
+            let on_window_change: OnWindowChange = Arc::new(move |new_window: Window| {
+                log::debug!("Active window changed: {:?}", new_window);
+                let _ = events.send(Event::WindowChanged(new_window.clone()));
+                active_window.swap(new_window.into());
+            });
+            let Some(mut window_source) = window_monitor::probe(&window_monitors, on_window_change.clone()) else {
+                if !window_monitors.contains(&WindowMonitorKind::X11) && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                    log::warn!("Active window tracking is unavailable: this Wayland compositor is not supported, application gestures are disabled");
+                } else {
+                    log::warn!("No window monitor available, application gestures are disabled");
+                }
+                return;
+            };
+
+            log::info!("Using {} window monitor", window_source.name());
+            window_source.run();
+            // Application gestures of the last known window must not stay active
+            on_window_change(Window::default());
+        }
+    });
+
+    let executor = Arc::new(SystemExecutor::default());
+    executor.prepare(&config.load());
+
     // Watch config file for changes
     std::thread::spawn({
         let config = config.clone();
+        let executor = executor.clone();
+        let config_path = config_path.clone();
         move || {
             let (tx, rx) = std::sync::mpsc::channel();
             let mut watcher = notify::recommended_watcher(tx).unwrap();
@@ -121,14 +355,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             let config_guard = config.load();
                             if event.paths.iter().any(|path| *path == config_path || config_guard.import.contains(path)) {
                                 log::info!("Config file changed, reloading...");
-                                match Config::parse_from_file(&config_path) {
-                                    Ok(new_config) => {
-                                        config.swap(new_config.into());
-                                        log::info!("Config reloaded successfully.");
-                                    }
-                                    Err(e) => {
-                                        log::error!("Failed to reload config file: {}", e);
-                                    }
+                                if let Err(e) = reload_config(&config, &config_path, executor.as_ref()) {
+                                    log::error!("Failed to reload config file: {}", e);
                                 }
                             }
                         }
@@ -141,66 +369,71 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
-    let touchpad_device = match get_touchpad_device() {
-        Some(device) => device,
-        None => {
-            log::error!("No touchpad device found.");
-            std::process::exit(1);
-        }
-    };
-    let touchpad_size = match get_touchpad_size(&touchpad_device) {
-        Ok(size) => size,
-        Err(e) => {
-            log::error!("Could not determine touchpad size: {}", e);
-            std::process::exit(1);
-        }
+    let mut touchpad = open_touchpad();
+    let touchpad_size = touchpad.touchpad_size();
+
+    let mut trace_writer = match &args.record {
+        Some(trace_path) => match TraceWriter::create(Path::new(trace_path), &touchpad_size) {
+            Ok(writer) => Some(writer),
+            Err(e) => {
+                log::error!("Failed to create trace file {}: {}", trace_path, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
     };
 
     let move_threshold_units = calculate_move_threshold_units(&touchpad_size, config.load().options.move_threshold);
 
-    let mut gestures_manager = GesturesEngine::new(config, active_window, move_threshold_units, touchpad_size);
+    let mut gestures_manager = GesturesEngine::new(config.clone(), active_window.clone(), move_threshold_units, touchpad_size, events.clone())
+        .with_executor(executor.clone());
 
-    let mut state: HashMap<u8, (Option<u16>, Option<u16>)> = HashMap::new();
-    let mut current_slot = 0u8;
+    let (control_tx, mut control_rx) = tokio::sync::mpsc::channel(16);
+    tokio::spawn(async move {
+        let socket_path = control::socket_path();
+        let result = match ControlSocket::bind(&socket_path) {
+            Ok(socket) => socket.serve(control_tx, events).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log::error!("Control socket {} failed: {}", socket_path.display(), e);
+        }
+    });
 
-    let mut event_stream = touchpad_device.into_event_stream().unwrap();
-    while let Ok(event) = event_stream.next_event().await {
-        match event.event_type() {
-            EventType::ABSOLUTE => {
-                match AbsoluteAxisCode(event.code()) {
-                    AbsoluteAxisCode::ABS_MT_SLOT => {
-                        current_slot = event.value() as u8;
-                    }
-                    AbsoluteAxisCode::ABS_MT_TRACKING_ID => {
-                        if event.value() == -1 {
-                            state.remove(&current_slot);
-                        } else {
-                            state.insert(current_slot, (None, None));
-                        }
-                    }
-                    AbsoluteAxisCode::ABS_MT_POSITION_X => {
-                        if let Some(position) = state.get_mut(&current_slot) {
-                            position.0 = Some(event.value() as u16);
-                        }
-                    }
-                    AbsoluteAxisCode::ABS_MT_POSITION_Y => {
-                        if let Some(position) = state.get_mut(&current_slot) {
-                            position.1 = Some(event.value() as u16);
-                        }
-                    }
-                    _ => {}
-                }
-            },
-            EventType::SYNCHRONIZATION => {
-                let mut filtered_state = State::default();
-                for (u8, (pos_x, pos_y)) in &state {
-                    if let (Some(x), Some(y)) = (pos_x, pos_y) {
-                        filtered_state.positions.insert(*u8, Position { x: *x, y: *y });
-                    }
-                }
-                gestures_manager.update_state(filtered_state);
-            },
-            _ => continue,
+    let mut slot_tracker = SlotTracker::default();
+    // Stopping through a signal returns from main, which drops the control socket and removes its file
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    loop {
+        let timeout = gestures_manager.next_timeout();
+        let event = tokio::select! {
+            event = touchpad.next_event() => event,
+            _ = tokio::time::sleep(timeout.unwrap_or_default().saturating_sub(timestamp(SystemTime::now()))), if timeout.is_some() => {
+                gestures_manager.update_timeout(timestamp(SystemTime::now()));
+                continue;
+            }
+            Some((request, reply)) = control_rx.recv() => {
+                let response = handle_control_request(request, &mut gestures_manager, executor.as_ref(), &config, &config_path, &active_window);
+                let _ = reply.send(response);
+                continue;
+            }
+            _ = interrupt.recv() => break,
+            _ = terminate.recv() => break,
+        };
+        let Ok(Some(event)) = event else {
+            break;
+        };
+
+        if let Some(writer) = &mut trace_writer
+            && let Err(e) = writer.write(&event)
+        {
+            log::error!("Failed to write trace file, recording stopped: {}", e);
+            trace_writer = None;
+        }
+
+        if let Some(state) = slot_tracker.handle_event(&event) {
+            gestures_manager.update_state(state);
         }
     }
 
@@ -0,0 +1,192 @@
+use std::io::Write;
+use std::path::Path;
+use crate::gestures::PerformedGesture;
+use crate::sequence_step::PerformedSequenceStep;
+
+/// Formats a performed gesture as an item of the `gestures:` list, measured values are added as comments
+pub fn gesture_yaml(name: &str, gesture: &PerformedGesture) -> String {
+    let mut yaml = format!("  - name: {}\n    sequence:\n", yaml_string(name));
+
+    for step in &gesture.sequence {
+        let fingers = step.slots().len();
+        let action = match step {
+            PerformedSequenceStep::Move { direction, .. } => format!("move {}", direction.as_str()),
+            PerformedSequenceStep::Pinch { direction, .. } => format!("pinch {}", direction.as_str()),
+            PerformedSequenceStep::Rotate { direction, .. } => format!("rotate {}", direction.as_str()),
+            PerformedSequenceStep::Hold { .. } => "hold".to_string(),
+            PerformedSequenceStep::TouchDown { .. } => "touch down".to_string(),
+            PerformedSequenceStep::TouchUp { .. } => "touch up".to_string(),
+        };
+
+        yaml.push_str(&format!("      - fingers: {}\n        action: {}", fingers, action));
+        match step {
+            PerformedSequenceStep::Move { distance, velocity, .. } => {
+                yaml.push_str(&format!(" # distance {:.2}, velocity {:.1}/s", distance, velocity));
+            }
+            PerformedSequenceStep::Pinch { scale, .. } => yaml.push_str(&format!(" # scale {:.2}", scale)),
+            PerformedSequenceStep::Rotate { angle, .. } => yaml.push_str(&format!(" # angle {:.0}", angle)),
+            _ => {}
+        }
+        yaml.push('\n');
+
+        if let PerformedSequenceStep::Hold { time, .. } = step {
+            yaml.push_str(&format!("        duration: {}ms\n", time.duration().as_millis()));
+        }
+    }
+
+    if let Some(edge) = gesture.edge {
+        yaml.push_str(&format!("    edge: {}\n", edge.as_str()));
+    }
+    yaml.push_str(&format!("    command: {}\n", yaml_string(&format!("echo {}", name))));
+    yaml
+}
+
+/// Quotes strings which YAML would otherwise misinterpret
+fn yaml_string(s: &str) -> String {
+    let is_plain = !s.is_empty()
+        && s.chars().all(|c| c.is_alphanumeric() || " _-.".contains(c))
+        && !s.starts_with(['-', ' ', '.'])
+        && !s.ends_with(' ');
+    if is_plain {
+        s.to_string()
+    } else {
+        // Single-quoted scalars have no escapes except a doubled quote, unlike Rust's debug format
+        format!("'{}'", s.replace('\'', "''"))
+    }
+}
+
+/// Appends gestures to the `gestures:` list of a config file, which must be the file's last top-level key
+pub fn append_to_config(path: &Path, gestures_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let content = if path.exists() { std::fs::read_to_string(path)? } else { String::new() };
+
+    let last_key = content.lines().rfind(|line| !line.starts_with([' ', '\t', '-', '#']) && !line.trim().is_empty());
+    let header = match last_key {
+        Some(key) if key.trim_end() == "gestures:" => "",
+        None => "gestures:\n",
+        Some(_) => {
+            return Err(format!("The last top-level key of {} is not 'gestures:', record into a separate file and import it instead", path.display()).into());
+        }
+    };
+
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    if !content.is_empty() && !content.ends_with('\n') {
+        file.write_all(b"\n")?;
+    }
+    file.write_all(header.as_bytes())?;
+    file.write_all(gestures_yaml.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::time::Duration;
+    use crate::config::{Direction, Edge, PinchDirection};
+    use crate::sequence_step::StepTime;
+
+    fn slots(count: u8) -> HashSet<u8> {
+        (0..count).collect()
+    }
+
+    fn time(start_ms: u64, end_ms: u64) -> StepTime {
+        StepTime { start: Duration::from_millis(start_ms), end: Duration::from_millis(end_ms) }
+    }
+
+    /// A config file in the temporary directory, removed when dropped
+    struct TempConfig(std::path::PathBuf);
+
+    impl TempConfig {
+        fn new(name: &str, content: Option<&str>) -> Self {
+            let path = std::env::temp_dir().join(format!("gest-test-{}-{}.yaml", std::process::id(), name));
+            match content {
+                Some(content) => std::fs::write(&path, content).unwrap(),
+                None => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+            TempConfig(path)
+        }
+
+        fn read(&self) -> String {
+            std::fs::read_to_string(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempConfig {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn gesture_yaml_of_every_step() {
+        let gesture = PerformedGesture {
+            sequence: vec![
+                PerformedSequenceStep::Hold { slots: slots(3), time: time(0, 750) },
+                PerformedSequenceStep::Move { slots: slots(3), direction: Direction::UpLeft, distance: 0.314, velocity: 1.83, time: time(750, 900) },
+                PerformedSequenceStep::Pinch { slots: slots(2), direction: PinchDirection::In, scale: 0.62, time: time(900, 1000) },
+            ],
+            edge: Some(Edge::Right),
+        };
+        assert_eq!(gesture_yaml("Close window", &gesture), "  - name: Close window
+    sequence:
+      - fingers: 3
+        action: hold
+        duration: 750ms
+      - fingers: 3
+        action: move up-left # distance 0.31, velocity 1.8/s
+      - fingers: 2
+        action: pinch in # scale 0.62
+    edge: right
+    command: echo Close window
+");
+    }
+
+    #[test]
+    fn gesture_yaml_quotes_names() {
+        let gesture = PerformedGesture {
+            sequence: vec![PerformedSequenceStep::TouchDown { slots: slots(3), time: time(0, 0) }],
+            edge: None,
+        };
+        let yaml = gesture_yaml("Bob's \"tab\": next \\ #1", &gesture);
+        assert!(yaml.starts_with("  - name: 'Bob''s \"tab\": next \\ #1'\n"), "{}", yaml);
+        assert!(yaml.ends_with("    command: 'echo Bob''s \"tab\": next \\ #1'\n"), "{}", yaml);
+
+        // The quoted name must read back as the original
+        let config: serde_yaml::Value = serde_yaml::from_str(&format!("gestures:\n{}", yaml)).unwrap();
+        assert_eq!(config["gestures"][0]["name"].as_str(), Some("Bob's \"tab\": next \\ #1"));
+    }
+
+    #[test]
+    fn yaml_strings() {
+        assert_eq!(yaml_string("Swipe left 3"), "Swipe left 3");
+        assert_eq!(yaml_string(""), "''");
+        assert_eq!(yaml_string("- item"), "'- item'");
+        assert_eq!(yaml_string("yes: no"), "'yes: no'");
+        assert_eq!(yaml_string("trailing "), "'trailing '");
+    }
+
+    #[test]
+    fn append_to_gestures_list() {
+        let config = TempConfig::new("append", Some("options:\n  move_threshold: 0.2\ngestures:\n  - name: A\n    sequence: []\n    command: a"));
+        append_to_config(&config.0, "  - name: B\n").unwrap();
+        assert_eq!(config.read(), "options:\n  move_threshold: 0.2\ngestures:\n  - name: A\n    sequence: []\n    command: a\n  - name: B\n");
+    }
+
+    #[test]
+    fn append_to_new_file() {
+        let config = TempConfig::new("new", None);
+        append_to_config(&config.0, "  - name: B\n").unwrap();
+        assert_eq!(config.read(), "gestures:\n  - name: B\n");
+    }
+
+    #[test]
+    fn append_after_another_key_fails() {
+        let content = "gestures:\n  - name: A\napplication_gestures:\n  firefox: []\n";
+        let config = TempConfig::new("other-key", Some(content));
+        let error = append_to_config(&config.0, "  - name: B\n").unwrap_err();
+        assert!(error.to_string().contains("is not 'gestures:'"), "{}", error);
+        assert_eq!(config.read(), content);
+    }
+}
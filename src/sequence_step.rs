@@ -1,6 +1,7 @@
 use std::fmt::{Formatter, Debug};
 use std::collections::{HashMap, HashSet};
-use crate::config::Direction;
+use std::time::Duration;
+use crate::config::{parse_duration_value, Direction, Options, PinchDirection, RotationDirection};
 
 #[derive(Debug, Clone)]
 pub enum Distance {
@@ -8,19 +9,93 @@ pub enum Distance {
     Fixed(f32),
 }
 
+/// Event timestamps of the first and last update of a performed step
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StepTime {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl StepTime {
+    pub fn at(time: Duration) -> Self {
+        StepTime { start: time, end: time }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+
+    fn within(&self, max_duration: &Option<Duration>) -> bool {
+        max_duration.is_none_or(|max| self.duration() <= max)
+    }
+}
+
+/// A step recognized from the fingers' movement, gestures match when their defined steps match the performed ones
 #[derive(Clone)]
 pub enum PerformedSequenceStep {
-    Move { slots: HashSet<u8>, direction: Direction, distance: f32 },
-    TouchUp { slots: HashSet<u8> },
-    TouchDown { slots: HashSet<u8> },
+    /// `velocity` is the peak speed of the centroid, in touchpad sizes per second
+    Move { slots: HashSet<u8>, direction: Direction, distance: f32, velocity: f32, time: StepTime },
+    Pinch { slots: HashSet<u8>, direction: PinchDirection, scale: f32, time: StepTime },
+    Rotate { slots: HashSet<u8>, direction: RotationDirection, angle: f32, time: StepTime },
+    Hold { slots: HashSet<u8>, time: StepTime },
+    TouchUp { slots: HashSet<u8>, time: StepTime },
+    TouchDown { slots: HashSet<u8>, time: StepTime },
+}
+
+impl PerformedSequenceStep {
+    pub fn slots(&self) -> &HashSet<u8> {
+        match self {
+            Self::Move { slots, .. }
+            | Self::Pinch { slots, .. }
+            | Self::Rotate { slots, .. }
+            | Self::Hold { slots, .. }
+            | Self::TouchUp { slots, .. }
+            | Self::TouchDown { slots, .. } => slots,
+        }
+    }
+
+    /// Direction of a move (e.g. `up-left`), pinch (`in`, `out`) or rotation (`cw`, `ccw`)
+    pub fn direction_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Move { direction, .. } => Some(direction.as_str()),
+            Self::Pinch { direction, .. } => Some(direction.as_str()),
+            Self::Rotate { direction, .. } => Some(direction.as_str()),
+            _ => None,
+        }
+    }
+
+    /// How far the step has progressed: distance of a move, relative scale change of a pinch, angle of a rotation or seconds of a hold
+    pub fn value(&self) -> Option<f32> {
+        match self {
+            Self::Move { distance, .. } => Some(*distance),
+            Self::Pinch { scale, .. } => Some((scale - 1.0).abs()),
+            Self::Rotate { angle, .. } => Some(*angle),
+            Self::Hold { time, .. } => Some(time.duration().as_secs_f32()),
+            Self::TouchUp { .. } | Self::TouchDown { .. } => None,
+        }
+    }
+
+    pub fn time(&self) -> StepTime {
+        match self {
+            Self::Move { time, .. }
+            | Self::Pinch { time, .. }
+            | Self::Rotate { time, .. }
+            | Self::Hold { time, .. }
+            | Self::TouchUp { time, .. }
+            | Self::TouchDown { time, .. } => *time,
+        }
+    }
 }
 
 impl Debug for PerformedSequenceStep {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::TouchDown { slots } => write!(f, "TouchDown({})", slots.len()),
-            Self::TouchUp { slots } => write!(f, "TouchUp({})", slots.len()),
-            Self::Move { slots, direction, distance } => write!(f, "Move{:?}({}, {})", direction, slots.len(), distance),
+            Self::TouchDown { slots, .. } => write!(f, "TouchDown({})", slots.len()),
+            Self::TouchUp { slots, .. } => write!(f, "TouchUp({})", slots.len()),
+            Self::Move { slots, direction, distance, velocity, time } => write!(f, "Move{:?}({}, {}, {}/s, {:?})", direction, slots.len(), distance, velocity, time.duration()),
+            Self::Pinch { slots, direction, scale, time } => write!(f, "Pinch{:?}({}, {}, {:?})", direction, slots.len(), scale, time.duration()),
+            Self::Rotate { slots, direction, angle, time } => write!(f, "Rotate{:?}({}, {}, {:?})", direction, slots.len(), angle, time.duration()),
+            Self::Hold { slots, time } => write!(f, "Hold({}, {:?})", slots.len(), time.duration()),
         }
     }
 }
@@ -29,38 +104,89 @@ impl Debug for PerformedSequenceStep {
 pub enum DefinedSequenceStep {
     TouchDown { fingers: u8 },
     TouchUp { fingers: u8 },
-    Move { fingers: u8, direction: Direction, distance: Option<f32> },
+    Move { fingers: u8, direction: Direction, distance: Option<f32>, max_duration: Option<Duration> },
+    Flick { fingers: u8, direction: Direction, distance: Option<f32>, velocity: f32, max_duration: Option<Duration> },
+    Pinch { fingers: u8, direction: PinchDirection, scale: Option<f32>, max_duration: Option<Duration> },
+    Rotate { fingers: u8, direction: RotationDirection, angle: Option<f32>, max_duration: Option<Duration> },
+    Hold { fingers: u8, duration: Duration },
 }
 
 #[derive(Debug, Clone)]
 pub enum DefinedSequenceStepRaw {
     TouchDown { fingers: u8 },
     TouchUp { fingers: u8 },
-    Move { fingers: u8, direction: Direction, distance: Option<Distance> },
+    Move { fingers: u8, direction: Direction, distance: Option<Distance>, max_duration: Option<Duration> },
+    Flick { fingers: u8, direction: Direction, distance: Option<Distance>, velocity: Option<f32>, max_duration: Option<Duration> },
+    Pinch { fingers: u8, direction: PinchDirection, scale: Option<f32>, max_duration: Option<Duration> },
+    Rotate { fingers: u8, direction: RotationDirection, angle: Option<f32>, max_duration: Option<Duration> },
+    Hold { fingers: u8, duration: Duration },
+}
+
+fn resolve_distance(distance: Option<Distance>, distances: &HashMap<String, f32>) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+    Ok(match distance {
+        Some(Distance::Variable(name)) => {
+            match distances.get(&name) {
+                Some(d) => Some(*d),
+                None => return Err(format!("Unknown distance: \"{}\"", name).into()),
+            }
+        }
+        Some(Distance::Fixed(d)) => Some(d),
+        _ => None,
+    })
 }
 
 impl DefinedSequenceStep {
-    pub fn from_raw(raw: DefinedSequenceStepRaw, distances: &HashMap<String, f32>) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn from_raw(raw: DefinedSequenceStepRaw, options: &Options) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(match raw {
             DefinedSequenceStepRaw::TouchDown { fingers } => DefinedSequenceStep::TouchDown { fingers },
             DefinedSequenceStepRaw::TouchUp { fingers } => DefinedSequenceStep::TouchUp { fingers },
-            DefinedSequenceStepRaw::Move { fingers, direction, distance } => {
-                let distance = match distance {
-                    Some(Distance::Variable(name)) => {
-                        match distances.get(&name) {
-                            Some(d) => Some(*d),
-                            None => return Err(format!("Unknown distance: \"{}\"", name).into()),
-                        }
-                    }
-                    Some(Distance::Fixed(d)) => Some(d),
-                    _ => None,
-                };
-                DefinedSequenceStep::Move { fingers, direction, distance }
+            DefinedSequenceStepRaw::Move { fingers, direction, distance, max_duration } => {
+                let distance = resolve_distance(distance, &options.distance)?;
+                DefinedSequenceStep::Move { fingers, direction, distance, max_duration }
+            }
+            DefinedSequenceStepRaw::Flick { fingers, direction, distance, velocity, max_duration } => {
+                let distance = resolve_distance(distance, &options.distance)?;
+                let velocity = velocity.unwrap_or(options.flick_velocity);
+                DefinedSequenceStep::Flick { fingers, direction, distance, velocity, max_duration }
             }
+            DefinedSequenceStepRaw::Pinch { fingers, direction, scale, max_duration } => DefinedSequenceStep::Pinch { fingers, direction, scale, max_duration },
+            DefinedSequenceStepRaw::Rotate { fingers, direction, angle, max_duration } => DefinedSequenceStep::Rotate { fingers, direction, angle, max_duration },
+            DefinedSequenceStepRaw::Hold { fingers, duration } => DefinedSequenceStep::Hold { fingers, duration },
         })
     }
 }
 
+impl DefinedSequenceStep {
+    /// Value of [`PerformedSequenceStep::value`] at which this step is complete
+    pub fn target_value(&self, options: &Options) -> Option<f32> {
+        match self {
+            Self::Move { distance, .. } | Self::Flick { distance, .. } => Some(distance.unwrap_or(options.move_threshold)),
+            Self::Pinch { scale, .. } => Some(scale.map_or(options.pinch_threshold, |s| (s - 1.0).abs())),
+            Self::Rotate { angle, .. } => Some(angle.unwrap_or(options.rotation_threshold)),
+            Self::Hold { duration, .. } => Some(duration.as_secs_f32()),
+            Self::TouchUp { .. } | Self::TouchDown { .. } => None,
+        }
+    }
+}
+
+impl DefinedSequenceStep {
+    /// Whether `step` is of this step's kind, direction and number of fingers, however far it got
+    pub fn can_become(&self, step: &PerformedSequenceStep) -> bool {
+        match (self, step) {
+            (Self::Move { fingers, direction, .. } | Self::Flick { fingers, direction, .. }, PerformedSequenceStep::Move { slots, direction: dir, .. }) => {
+                *fingers as usize == slots.len() && direction == dir
+            }
+            (Self::Pinch { fingers, direction, .. }, PerformedSequenceStep::Pinch { slots, direction: dir, .. }) => {
+                *fingers as usize == slots.len() && direction == dir
+            }
+            (Self::Rotate { fingers, direction, .. }, PerformedSequenceStep::Rotate { slots, direction: dir, .. }) => {
+                *fingers as usize == slots.len() && direction == dir
+            }
+            _ => false,
+        }
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for DefinedSequenceStepRaw {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -85,16 +211,69 @@ impl<'de> serde::Deserialize<'de> for DefinedSequenceStepRaw {
             return Err(serde::de::Error::custom(format!("Distance must be between 0 and 1, got {}", d)));
         }
 
+        let scale = map.get("scale")
+            .and_then(|v| v.as_f64())
+            .map(|f| f as f32);
+        let angle = map.get("angle")
+            .and_then(|v| v.as_f64())
+            .map(|f| f as f32);
+
+        if let Some(a) = angle
+            && a <= 0f32
+        {
+            return Err(serde::de::Error::custom(format!("Angle must be greater than 0, got {}", a)));
+        }
+
+        let duration = map.get("duration")
+            .map(parse_duration_value)
+            .transpose()
+            .map_err(serde::de::Error::custom)?;
+        let max_duration = map.get("max_duration")
+            .map(parse_duration_value)
+            .transpose()
+            .map_err(serde::de::Error::custom)?;
+        let velocity = map.get("velocity")
+            .and_then(|v| v.as_f64())
+            .map(|f| f as f32);
+
+        let flick_direction = action.strip_prefix("flick_")
+            .or_else(|| action.strip_prefix("flick "))
+            .and_then(Direction::parse);
+        if let Some(direction) = flick_direction {
+            return Ok(DefinedSequenceStepRaw::Flick { fingers, direction, distance, velocity, max_duration });
+        }
+
+        let move_direction = action.strip_prefix("move_")
+            .or_else(|| action.strip_prefix("move "))
+            .and_then(Direction::parse);
+        if let Some(direction) = move_direction {
+            return Ok(DefinedSequenceStepRaw::Move { fingers, direction, distance, max_duration });
+        }
+
         let step = match action {
             "touch_down" | "touch down" => DefinedSequenceStepRaw::TouchDown { fingers },
             "touch_up" | "touch up" => DefinedSequenceStepRaw::TouchUp { fingers },
-            "move_up" | "move up" => DefinedSequenceStepRaw::Move { fingers, direction: Direction::Up, distance },
-            "move_down" | "move down" => DefinedSequenceStepRaw::Move { fingers, direction: Direction::Down, distance },
-            "move_left" | "move left" => DefinedSequenceStepRaw::Move { fingers, direction: Direction::Left, distance },
-            "move_right" | "move right" => DefinedSequenceStepRaw::Move { fingers, direction: Direction::Right, distance },
+            "pinch_in" | "pinch in" => DefinedSequenceStepRaw::Pinch { fingers, direction: PinchDirection::In, scale, max_duration },
+            "pinch_out" | "pinch out" => DefinedSequenceStepRaw::Pinch { fingers, direction: PinchDirection::Out, scale, max_duration },
+            "rotate_cw" | "rotate cw" => DefinedSequenceStepRaw::Rotate { fingers, direction: RotationDirection::Clockwise, angle, max_duration },
+            "rotate_ccw" | "rotate ccw" => DefinedSequenceStepRaw::Rotate { fingers, direction: RotationDirection::CounterClockwise, angle, max_duration },
+            "hold" => DefinedSequenceStepRaw::Hold {
+                fingers,
+                duration: duration.ok_or_else(|| serde::de::Error::custom("Missing or invalid 'duration' field for hold action"))?,
+            },
             _ => return Err(serde::de::Error::custom(format!("Unknown action: {}", action))),
         };
 
+        match (&step, scale) {
+            (DefinedSequenceStepRaw::Pinch { direction: PinchDirection::In, .. }, Some(s)) if !(0f32..1f32).contains(&s) => {
+                return Err(serde::de::Error::custom(format!("Pinch in scale must be between 0 and 1, got {}", s)));
+            }
+            (DefinedSequenceStepRaw::Pinch { direction: PinchDirection::Out, .. }, Some(s)) if s <= 1f32 => {
+                return Err(serde::de::Error::custom(format!("Pinch out scale must be greater than 1, got {}", s)));
+            }
+            _ => {}
+        }
+
         Ok(step)
     }
 }
@@ -102,8 +281,8 @@ impl<'de> serde::Deserialize<'de> for DefinedSequenceStepRaw {
 impl PartialEq<PerformedSequenceStep> for DefinedSequenceStep {
     fn eq(&self, other: &PerformedSequenceStep) -> bool {
         match (self, other) {
-            (DefinedSequenceStep::Move { fingers, direction, distance }, PerformedSequenceStep::Move { slots, direction: dir, distance: dst }) => {
-                if *fingers as usize != slots.len() || direction != dir {
+            (DefinedSequenceStep::Move { fingers, direction, distance, max_duration }, PerformedSequenceStep::Move { slots, direction: dir, distance: dst, time, .. }) => {
+                if *fingers as usize != slots.len() || direction != dir || !time.within(max_duration) {
                     return false;
                 }
 
@@ -113,12 +292,50 @@ impl PartialEq<PerformedSequenceStep> for DefinedSequenceStep {
                     return false;
                 }
             }
-            (DefinedSequenceStep::TouchUp { fingers }, PerformedSequenceStep::TouchUp { slots }) => {
+            (DefinedSequenceStep::Flick { fingers, direction, distance, velocity, max_duration }, PerformedSequenceStep::Move { slots, direction: dir, distance: dst, velocity: vel, time }) => {
+                if *fingers as usize != slots.len() || direction != dir || vel < velocity || !time.within(max_duration) {
+                    return false;
+                }
+
+                if let Some(d) = distance
+                    && dst < d
+                {
+                    return false;
+                }
+            }
+            (DefinedSequenceStep::Pinch { fingers, direction, scale, max_duration }, PerformedSequenceStep::Pinch { slots, direction: dir, scale: scl, time }) => {
+                if *fingers as usize != slots.len() || direction != dir || !time.within(max_duration) {
+                    return false;
+                }
+
+                match (direction, scale) {
+                    (PinchDirection::In, Some(s)) if scl > s => return false,
+                    (PinchDirection::Out, Some(s)) if scl < s => return false,
+                    _ => {}
+                }
+            }
+            (DefinedSequenceStep::Rotate { fingers, direction, angle, max_duration }, PerformedSequenceStep::Rotate { slots, direction: dir, angle: ang, time }) => {
+                if *fingers as usize != slots.len() || direction != dir || !time.within(max_duration) {
+                    return false;
+                }
+
+                if let Some(a) = angle
+                    && ang < a
+                {
+                    return false;
+                }
+            }
+            (DefinedSequenceStep::Hold { fingers, duration }, PerformedSequenceStep::Hold { slots, time }) => {
+                if *fingers as usize != slots.len() || time.duration() < *duration {
+                    return false;
+                }
+            }
+            (DefinedSequenceStep::TouchUp { fingers }, PerformedSequenceStep::TouchUp { slots, .. }) => {
                 if *fingers as usize != slots.len() {
                     return false;
                 }
             }
-            (DefinedSequenceStep::TouchDown { fingers }, PerformedSequenceStep::TouchDown { slots }) => {
+            (DefinedSequenceStep::TouchDown { fingers }, PerformedSequenceStep::TouchDown { slots, .. }) => {
                 if *fingers as usize != slots.len() {
                     return false;
                 }
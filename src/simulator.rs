@@ -0,0 +1,654 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use arc_swap::ArcSwap;
+use tokio::sync::broadcast;
+use crate::config::{parse_duration, Config};
+use crate::events::{Event, EVENT_BUFFER_SIZE};
+use crate::executor::{ActionExecutor, ExecutedAction, RecordingExecutor};
+use crate::gestures::{GesturesEngine, MoveThresholdUnits, Position, State};
+use crate::input::calculate_move_threshold_units;
+use crate::Window;
+
+/// Size of the simulated touchpad, scripts use fractions of it
+pub const SIMULATED_TOUCHPAD_SIZE: MoveThresholdUnits = MoveThresholdUnits { x: 1000, y: 1000 };
+/// Time between two frames of a simulated movement
+const FRAME_INTERVAL: Duration = Duration::from_millis(10);
+/// Horizontal distance between simulated fingers, as a fraction of the touchpad width
+const FINGER_SPACING: f32 = 0.08;
+/// Duration of movements whose script line doesn't specify one
+const DEFAULT_MOVE_DURATION: Duration = Duration::from_millis(200);
+
+/// Drives a gestures engine with synthesized touchpad states instead of a device, timeouts fire at simulated time.
+/// Actions of matched gestures are recorded instead of being executed.
+pub struct Simulator {
+    engine: GesturesEngine,
+    executor: Arc<RecordingExecutor>,
+    events: broadcast::Receiver<Event>,
+    active_window: Arc<ArcSwap<Window>>,
+    touchpad_size: MoveThresholdUnits,
+    /// positions of the fingers down, as fractions of the touchpad size
+    fingers: HashMap<u8, (f32, f32)>,
+    time: Duration,
+    /// events produced so far and not yet taken
+    recorded_events: Vec<Event>,
+}
+
+impl Simulator {
+    pub fn new(config: Arc<ArcSwap<Config>>, touchpad_size: MoveThresholdUnits) -> Self {
+        let move_threshold_units = calculate_move_threshold_units(&touchpad_size, config.load().options.move_threshold);
+        let (sender, events) = broadcast::channel(EVENT_BUFFER_SIZE);
+        let active_window = Arc::new(ArcSwap::new(Window::default().into()));
+        let executor = Arc::new(RecordingExecutor::default());
+        let engine = GesturesEngine::new(config, active_window.clone(), move_threshold_units, touchpad_size, sender)
+            .with_executor(executor.clone());
+
+        Simulator {
+            engine,
+            executor,
+            events,
+            active_window,
+            touchpad_size,
+            fingers: HashMap::new(),
+            time: Duration::ZERO,
+            recorded_events: Vec::new(),
+        }
+    }
+
+    /// Executes the actions of matched gestures with another executor, they are not recorded anymore
+    pub fn with_executor(mut self, executor: Arc<dyn ActionExecutor>) -> Self {
+        self.engine = self.engine.with_executor(executor);
+        self
+    }
+
+    /// Actions of matched gestures since the last call
+    pub fn take_executed(&mut self) -> Vec<ExecutedAction> {
+        self.executor.take()
+    }
+
+    /// Sets the window the application gestures and `when` conditions are matched against
+    pub fn set_window(&mut self, window: Window) {
+        self.active_window.store(window.into());
+    }
+
+    /// Passes a state to the engine, after firing the timeouts which are due before it
+    pub fn feed(&mut self, state: State) {
+        self.advance_to(state.time);
+        self.engine.update_state(state);
+        self.collect_events();
+    }
+
+    /// Fires the timeouts which are due until `time`
+    pub fn advance_to(&mut self, time: Duration) {
+        while let Some(timeout) = self.engine.next_timeout()
+            && timeout <= time
+        {
+            self.engine.update_timeout(timeout);
+            self.collect_events();
+        }
+        self.time = self.time.max(time);
+    }
+
+    /// Fires all pending timeouts, e.g. to complete taps which wait for another tap
+    pub fn finish(&mut self) {
+        while let Some(timeout) = self.engine.next_timeout() {
+            self.engine.update_timeout(timeout);
+            self.collect_events();
+            self.time = self.time.max(timeout);
+        }
+    }
+
+    pub fn is_touching(&self) -> bool {
+        !self.fingers.is_empty()
+    }
+
+    /// Events produced since the last call
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.recorded_events)
+    }
+
+    fn collect_events(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            self.recorded_events.push(event);
+        }
+    }
+
+    /// Puts fingers down next to each other, centered on a point given as fractions of the touchpad size
+    pub fn touch_down(&mut self, fingers: usize, x: f32, y: f32) {
+        let first_slot = self.fingers.keys().max().map_or(0, |slot| slot + 1);
+        for i in 0..fingers {
+            let offset = (i as f32 - (fingers - 1) as f32 / 2.0) * FINGER_SPACING;
+            self.fingers.insert(first_slot + i as u8, (x + offset, y));
+        }
+        self.time += FRAME_INTERVAL;
+        self.feed_fingers();
+    }
+
+    /// Lifts all fingers
+    pub fn lift(&mut self) {
+        self.fingers.clear();
+        self.time += FRAME_INTERVAL;
+        self.feed_fingers();
+    }
+
+    /// Moves all fingers by fractions of the touchpad size, at constant speed
+    pub fn move_by(&mut self, dx: f32, dy: f32, duration: Duration) {
+        self.animate(duration, |(x, y), _, progress| (x + dx * progress, y + dy * progress));
+    }
+
+    /// Scales the distance of the fingers from their centroid
+    pub fn pinch(&mut self, scale: f32, duration: Duration) {
+        self.animate(duration, |(x, y), (cx, cy), progress| {
+            let factor = 1.0 + (scale - 1.0) * progress;
+            (cx + (x - cx) * factor, cy + (y - cy) * factor)
+        });
+    }
+
+    /// Turns the fingers around their centroid, positive angles in degrees are clockwise
+    pub fn rotate(&mut self, angle: f32, duration: Duration) {
+        // Touchpad units are used so that the rotation is not distorted on a touchpad which isn't square
+        let (width, height) = (self.touchpad_size.x as f32, self.touchpad_size.y as f32);
+        self.animate(duration, |(x, y), (cx, cy), progress| {
+            let (sin, cos) = (angle * progress).to_radians().sin_cos();
+            let (dx, dy) = ((x - cx) * width, (y - cy) * height);
+            (cx + (dx * cos - dy * sin) / width, cy + (dx * sin + dy * cos) / height)
+        });
+    }
+
+    /// Keeps the fingers still, the touchpad doesn't report anything meanwhile
+    pub fn wait(&mut self, duration: Duration) {
+        self.time += duration;
+        self.advance_to(self.time);
+    }
+
+    /// Feeds one frame per `FRAME_INTERVAL`, with finger positions computed from their start position,
+    /// the start centroid and the progress from 0 to 1
+    fn animate(&mut self, duration: Duration, position: impl Fn((f32, f32), (f32, f32), f32) -> (f32, f32)) {
+        let start = self.fingers.clone();
+        let count = start.len().max(1) as f32;
+        let centroid = start.values().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x / count, sy + y / count));
+        let frames = (duration.as_micros() / FRAME_INTERVAL.as_micros()).max(1) as u32;
+
+        for frame in 1..=frames {
+            let progress = frame as f32 / frames as f32;
+            for (slot, start_position) in &start {
+                self.fingers.insert(*slot, position(*start_position, centroid, progress));
+            }
+            self.time += duration / frames;
+            self.feed_fingers();
+        }
+    }
+
+    fn feed_fingers(&mut self) {
+        let (width, height) = (self.touchpad_size.x as f32, self.touchpad_size.y as f32);
+        let positions = self.fingers.iter()
+            .map(|(slot, (x, y))| {
+                let x = (x * width).clamp(0.0, width - 1.0) as u16;
+                let y = (y * height).clamp(0.0, height - 1.0) as u16;
+                (*slot, Position { x, y })
+            })
+            .collect();
+        self.feed(State { positions, time: self.time });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptStep {
+    Down { fingers: usize, x: f32, y: f32 },
+    Up,
+    Move { dx: f32, dy: f32, duration: Duration },
+    Pinch { scale: f32, duration: Duration },
+    Rotate { angle: f32, duration: Duration },
+    Wait(Duration),
+    Window { class: String },
+    /// the next gesture matched since the previous expectation, `None` if no gesture must have matched
+    Expect(Option<String>),
+}
+
+/// Lines of a simulation script, e.g.
+/// ```text
+/// down 3 at 0.5 0.5
+/// move left 30% in 150ms
+/// up
+/// expect Previous workspace
+/// ```
+pub fn parse_script(script: &str) -> Result<Vec<ScriptStep>, String> {
+    script.lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| parse_script_line(line).map_err(|e| format!("Line {}: {}", index + 1, e)))
+        .collect()
+}
+
+fn parse_script_line(line: &str) -> Result<ScriptStep, String> {
+    let (command, argument) = line.split_once(' ').map_or((line, ""), |(command, argument)| (command, argument.trim()));
+    // Movements take an optional duration at the end
+    let (argument, duration) = match argument.split_once(" in ") {
+        Some((argument, duration)) if matches!(command, "move" | "pinch" | "rotate") => (argument.trim(), parse_duration(duration.trim())?),
+        _ => (argument, DEFAULT_MOVE_DURATION),
+    };
+    let words = argument.split_whitespace().collect::<Vec<_>>();
+
+    Ok(match (command, words.as_slice()) {
+        ("down", [fingers]) => ScriptStep::Down { fingers: parse_fingers(fingers)?, x: 0.5, y: 0.5 },
+        ("down", [fingers, "at", x, y]) => ScriptStep::Down { fingers: parse_fingers(fingers)?, x: parse_fraction(x)?, y: parse_fraction(y)? },
+        ("up", []) => ScriptStep::Up,
+        ("move", ["by", dx, dy]) => ScriptStep::Move { dx: parse_fraction(dx)?, dy: parse_fraction(dy)?, duration },
+        ("move", [direction, distance]) => {
+            let distance = parse_fraction(distance)?;
+            let (dx, dy) = match *direction {
+                "left" => (-1.0, 0.0),
+                "right" => (1.0, 0.0),
+                "up" => (0.0, -1.0),
+                "down" => (0.0, 1.0),
+                "up-left" => (-1.0, -1.0),
+                "up-right" => (1.0, -1.0),
+                "down-left" => (-1.0, 1.0),
+                "down-right" => (1.0, 1.0),
+                _ => return Err(format!("Unknown direction: '{}'", direction)),
+            };
+            ScriptStep::Move { dx: dx * distance, dy: dy * distance, duration }
+        }
+        ("pinch", [scale]) => ScriptStep::Pinch { scale: parse_number(scale)?, duration },
+        ("rotate", [angle]) => ScriptStep::Rotate { angle: parse_number(angle)?, duration },
+        ("rotate", [angle, "cw"]) => ScriptStep::Rotate { angle: parse_number(angle)?, duration },
+        ("rotate", [angle, "ccw"]) => ScriptStep::Rotate { angle: -parse_number(angle)?, duration },
+        ("wait", [duration]) => ScriptStep::Wait(parse_duration(duration)?),
+        ("window", _) if !argument.is_empty() => ScriptStep::Window { class: argument.to_string() },
+        ("expect", ["none"]) => ScriptStep::Expect(None),
+        ("expect", _) if !argument.is_empty() => ScriptStep::Expect(Some(argument.to_string())),
+        _ => return Err(format!("Invalid step: '{}'", line)),
+    })
+}
+
+fn parse_fingers(s: &str) -> Result<usize, String> {
+    s.parse().ok().filter(|fingers| *fingers > 0).ok_or_else(|| format!("Invalid number of fingers: '{}'", s))
+}
+
+fn parse_number(s: &str) -> Result<f32, String> {
+    s.parse().map_err(|_| format!("Invalid number: '{}'", s))
+}
+
+/// Parses a fraction of the touchpad size, like `0.3` or `30%`
+fn parse_fraction(s: &str) -> Result<f32, String> {
+    match s.strip_suffix('%') {
+        Some(percent) => Ok(parse_number(percent)? / 100.0),
+        None => parse_number(s),
+    }
+}
+
+/// Runs a script, returning the failed expectations. Gestures matched but not expected are failures too.
+pub fn run_script(simulator: &mut Simulator, steps: &[ScriptStep]) -> Vec<String> {
+    let mut failures = Vec::new();
+    let mut matched = VecDeque::new();
+
+    for step in steps {
+        match step {
+            ScriptStep::Down { fingers, x, y } => simulator.touch_down(*fingers, *x, *y),
+            ScriptStep::Up => simulator.lift(),
+            ScriptStep::Move { dx, dy, duration } => simulator.move_by(*dx, *dy, *duration),
+            ScriptStep::Pinch { scale, duration } => simulator.pinch(*scale, *duration),
+            ScriptStep::Rotate { angle, duration } => simulator.rotate(*angle, *duration),
+            ScriptStep::Wait(duration) => simulator.wait(*duration),
+            ScriptStep::Window { class } => simulator.set_window(Window { class: class.clone(), ..Default::default() }),
+            ScriptStep::Expect(expected) => {
+                // Taps are only matched once no further tap can follow
+                if !simulator.is_touching() {
+                    simulator.finish();
+                }
+                matched.extend(matched_gestures(simulator.take_events()));
+                match (expected, matched.pop_front()) {
+                    (Some(expected), Some(name)) if *expected == name => {}
+                    (Some(expected), Some(name)) => failures.push(format!("expected {}, matched {}", expected, name)),
+                    (Some(expected), None) => failures.push(format!("expected {}, no gesture matched", expected)),
+                    (None, Some(name)) => failures.push(format!("expected none, matched {}", name)),
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+
+    simulator.finish();
+    matched.extend(matched_gestures(simulator.take_events()));
+    failures.extend(matched.into_iter().map(|name| format!("unexpected {}", name)));
+    failures
+}
+
+fn matched_gestures(events: Vec<Event>) -> impl Iterator<Item = String> {
+    events.into_iter().filter_map(|event| match event {
+        Event::GestureMatched { name } => Some(name),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence_step::PerformedSequenceStep;
+
+    /// Gestures without an `options:` section, so that the defaults are used
+    const CONFIG: &str = r#"
+gestures:
+  - name: Swipe left
+    sequence:
+      - fingers: 3
+        action: move left
+    command: swipe-left
+  - name: Swipe up-right
+    sequence:
+      - fingers: 3
+        action: move up-right
+    command: swipe-up-right
+  - name: Swipe left 4
+    sequence:
+      - fingers: 4
+        action: move left
+    command: swipe-left-4
+  - name: Flick left 4
+    sequence:
+      - fingers: 4
+        action: flick left
+    command: flick-left-4
+  - name: Tap
+    sequence:
+      - fingers: 3
+        action: touch down
+      - fingers: 3
+        action: touch up
+    command: tap
+  - name: Double tap 2
+    sequence:
+      - fingers: 2
+        action: touch down
+      - fingers: 2
+        action: touch up
+      - fingers: 2
+        action: touch down
+      - fingers: 2
+        action: touch up
+    command: double-tap
+  - name: Hold 4
+    sequence:
+      - fingers: 4
+        action: hold
+        duration: 300ms
+    command: hold
+  - name: Pinch in
+    sequence:
+      - fingers: 2
+        action: pinch in
+    command: pinch-in
+  - name: Rotate cw
+    sequence:
+      - fingers: 2
+        action: rotate cw
+    command: rotate-cw
+"#;
+
+    fn simulator(config: &str) -> Simulator {
+        let config = Config::parse(config, "config.yaml").unwrap();
+        Simulator::new(Arc::new(ArcSwap::from_pointee(config)), SIMULATED_TOUCHPAD_SIZE)
+    }
+
+    fn assert_script(config: &str, script: &str) {
+        let failures = run_script(&mut simulator(config), &parse_script(script).unwrap());
+        assert!(failures.is_empty(), "{:?}", failures);
+    }
+
+    fn env<'a>(action: &'a ExecutedAction, name: &str) -> &'a str {
+        action.env.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str()).unwrap_or_default()
+    }
+
+    #[test]
+    fn swipe() {
+        assert_script(CONFIG, "down 3\nmove left 0.3\nup\nexpect Swipe left");
+    }
+
+    #[test]
+    fn diagonal_swipe() {
+        assert_script(CONFIG, "down 3\nmove up-right 25%\nup\nexpect Swipe up-right");
+    }
+
+    #[test]
+    fn swipe_in_another_direction_matches_nothing() {
+        assert_script(CONFIG, "down 3\nmove down 0.3\nup\nexpect none");
+    }
+
+    #[test]
+    fn tap() {
+        assert_script(CONFIG, "down 3\nup\nexpect Tap");
+    }
+
+    #[test]
+    fn long_press_is_not_a_tap() {
+        assert_script(CONFIG, "down 3\nwait 1s\nup\nexpect none");
+    }
+
+    #[test]
+    fn double_tap() {
+        assert_script(CONFIG, "down 2\nup\nwait 100ms\ndown 2\nup\nexpect Double tap 2");
+    }
+
+    #[test]
+    fn taps_too_far_apart_are_not_a_double_tap() {
+        assert_script(CONFIG, "down 2\nup\nwait 500ms\ndown 2\nup\nexpect none");
+    }
+
+    #[test]
+    fn hold() {
+        assert_script(CONFIG, "down 4\nwait 400ms\nexpect Hold 4\nup\nexpect none");
+    }
+
+    #[test]
+    fn moving_after_a_hold_matches_nothing_else() {
+        assert_script(CONFIG, "down 4\nwait 400ms\nexpect Hold 4\nmove left 0.3\nup\nexpect none");
+    }
+
+    #[test]
+    fn hold_too_short() {
+        assert_script(CONFIG, "down 4\nwait 200ms\nup\nexpect none");
+    }
+
+    #[test]
+    fn recording_only_turns_long_rests_into_holds() {
+        let mut simulator = simulator(CONFIG);
+        simulator.engine.start_recording();
+        run_script(&mut simulator, &parse_script("down 3\nwait 300ms\nmove left 0.3\nup\nwait 1s\ndown 3\nwait 800ms\nmove left 0.3\nup").unwrap());
+        let recorded = simulator.engine.take_recorded();
+        assert_eq!(recorded.len(), 2);
+
+        assert!(matches!(recorded[0].sequence[..], [PerformedSequenceStep::Move { .. }]));
+        let [PerformedSequenceStep::Hold { time, .. }, PerformedSequenceStep::Move { .. }] = &recorded[1].sequence[..] else {
+            panic!("{:?}", recorded[1].sequence);
+        };
+        assert_eq!(time.duration(), Duration::from_millis(750));
+    }
+
+    #[test]
+    fn pinch() {
+        assert_script(CONFIG, "down 2\npinch 0.5\nup\nexpect Pinch in");
+    }
+
+    #[test]
+    fn pinch_with_one_finger_anchored() {
+        // The centroid travels half as far as the moving finger, which must not make the pinch a move
+        let mut simulator = simulator(CONFIG);
+        simulator.fingers = HashMap::from([(0, (0.2, 0.5)), (1, (0.8, 0.5))]);
+        simulator.time += FRAME_INTERVAL;
+        simulator.feed_fingers();
+        simulator.animate(DEFAULT_MOVE_DURATION, |(x, y), _, progress| if x > 0.5 { (x - 0.5 * progress, y) } else { (x, y) });
+        simulator.lift();
+        simulator.finish();
+        assert_eq!(matched_gestures(simulator.take_events()).collect::<Vec<_>>(), ["Pinch in"]);
+    }
+
+    #[test]
+    fn rotate() {
+        assert_script(CONFIG, "down 2\nrotate 40 cw\nup\nexpect Rotate cw");
+    }
+
+    #[test]
+    fn rotate_with_drifting_centroid() {
+        // Turning around one finger drags the centroid along, which must not make the rotation a move
+        let mut simulator = simulator(CONFIG);
+        simulator.fingers = HashMap::from([(0, (0.25, 0.3)), (1, (0.75, 0.3))]);
+        simulator.time += FRAME_INTERVAL;
+        simulator.feed_fingers();
+        simulator.animate(DEFAULT_MOVE_DURATION, |(x, y), _, progress| {
+            let (sin, cos) = (60f32 * progress).to_radians().sin_cos();
+            let (dx, dy) = (x - 0.25, y - 0.3);
+            (0.25 + dx * cos - dy * sin, 0.3 + dx * sin + dy * cos)
+        });
+        simulator.lift();
+        simulator.finish();
+        assert_eq!(matched_gestures(simulator.take_events()).collect::<Vec<_>>(), ["Rotate cw"]);
+    }
+
+    #[test]
+    fn flick() {
+        assert_script(CONFIG, "down 4\nmove left 0.3 in 60ms\nup\nexpect Flick left 4");
+    }
+
+    #[test]
+    fn ordinary_swipe_is_not_a_flick() {
+        assert_script(CONFIG, "down 4\nmove left 0.4 in 150ms\nup\nexpect Swipe left 4");
+    }
+
+    #[test]
+    fn flick_only_replaces_moves_with_its_fingers() {
+        // A 3 finger swipe as fast as a flick is still a swipe, the flick is for 4 fingers
+        assert_script(CONFIG, "down 3\nmove left 0.3 in 60ms\nup\nexpect Swipe left");
+    }
+
+    #[test]
+    fn options_section_is_optional() {
+        let options = Config::parse(CONFIG, "config.yaml").unwrap().options;
+        assert!(options.move_threshold > 0.0);
+        assert!(options.pinch_threshold > 0.0);
+        assert!(options.rotation_threshold > 0.0);
+        assert!(options.flick_velocity > 0.0);
+        assert!(options.edge.threshold > 0.0);
+        assert!(options.tap_interval > Duration::ZERO);
+    }
+
+    #[test]
+    fn swipe_without_options_section() {
+        // With thresholds of 0, every frame of a multi-finger swipe was a pinch and a rotation
+        let mut simulator = simulator(CONFIG);
+        simulator.touch_down(3, 0.5, 0.5);
+        simulator.move_by(-0.3, 0.0, DEFAULT_MOVE_DURATION);
+        simulator.lift();
+        simulator.finish();
+        let executed = simulator.take_executed();
+        assert_eq!(executed.len(), 1);
+        assert_eq!(executed[0].action, crate::GestureAction::Command("swipe-left".to_string()));
+    }
+
+    #[test]
+    fn rest_with_other_fingers_than_a_hold() {
+        // Only the 4 finger hold is configured, resting 3 fingers must not keep the swipe from matching
+        assert_script(CONFIG, "down 3\nwait 500ms\nmove left 0.3\nup\nexpect Swipe left");
+    }
+
+    #[test]
+    fn rest_before_a_swipe_with_the_fingers_of_a_hold() {
+        let config = CONFIG.replace("duration: 300ms", "duration: 2s");
+        assert_script(&config, "down 4\nwait 500ms\nmove left 0.3\nup\nexpect Swipe left 4");
+    }
+
+    #[test]
+    fn application_gestures() {
+        let config = format!("{}application_gestures:\n  firefox:\n    - name: Back\n      sequence:\n        - fingers: 2\n          action: move right\n      command: back\n", CONFIG);
+        assert_script(&config, "down 2\nmove right 0.3\nup\nexpect none\nwindow firefox\ndown 2\nmove right 0.3\nup\nexpect Back");
+    }
+
+    #[test]
+    fn executed_actions_get_the_gesture_env() {
+        let mut simulator = simulator(CONFIG);
+        run_script(&mut simulator, &parse_script("down 3\nmove left 0.3\nup").unwrap());
+        let executed = simulator.take_executed();
+        assert_eq!(executed.len(), 1);
+        assert_eq!(env(&executed[0], "GEST_NAME"), "Swipe left");
+        assert_eq!(env(&executed[0], "GEST_FINGERS"), "3");
+        assert_eq!(env(&executed[0], "GEST_DIRECTION"), "left");
+        assert_eq!(env(&executed[0], "GEST_REPEAT_COUNT"), "1");
+    }
+
+    #[test]
+    fn consecutive_taps_count_as_repeats() {
+        let mut simulator = simulator(CONFIG);
+        run_script(&mut simulator, &parse_script("down 3\nup\nwait 100ms\ndown 3\nup\nwait 1s\ndown 3\nup").unwrap());
+        let counts = simulator.take_executed().iter().map(|action| env(action, "GEST_REPEAT_COUNT").to_string()).collect::<Vec<_>>();
+        assert_eq!(counts, ["1", "2", "1"]);
+    }
+
+    #[test]
+    fn another_gesture_ends_the_repeats() {
+        let config = "gestures:\n  - name: Left\n    sequence:\n      - fingers: 3\n        action: move left\n    repeat_mode: slide\n    command: left\n  - name: Right\n    sequence:\n      - fingers: 3\n        action: move right\n    repeat_mode: slide\n    command: right\n";
+        let mut simulator = simulator(config);
+        simulator.touch_down(3, 0.8, 0.5);
+        simulator.move_by(-0.2, 0.0, DEFAULT_MOVE_DURATION);
+        simulator.move_by(-0.2, 0.0, DEFAULT_MOVE_DURATION);
+        simulator.engine.trigger("Right");
+        simulator.move_by(-0.2, 0.0, DEFAULT_MOVE_DURATION);
+        simulator.lift();
+        let counts = simulator.take_executed().iter()
+            .map(|action| format!("{} {}", env(action, "GEST_NAME"), env(action, "GEST_REPEAT_COUNT")))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, ["Left 1", "Left 2", "Right 1", "Left 1"]);
+    }
+
+    #[test]
+    fn continuous_phases_share_the_repeat_count() {
+        let config = "gestures:\n  - name: Scrub\n    sequence:\n      - fingers: 3\n        action: move right\n    repeat_mode: continuous\n    command: scrub\n";
+        let mut simulator = simulator(config);
+        run_script(&mut simulator, &parse_script("down 3\nmove right 0.3\nup").unwrap());
+        let executed = simulator.take_executed();
+        assert!(executed.len() > 2);
+        assert!(executed.iter().all(|action| env(action, "GEST_REPEAT_COUNT") == "1"));
+    }
+
+    #[test]
+    fn continuous_gesture_follows_the_fingers_back() {
+        let config = "gestures:\n  - name: Scrub\n    sequence:\n      - fingers: 3\n        action: move right\n    repeat_mode: continuous\n    command: scrub\n";
+        let mut simulator = simulator(config);
+        run_script(&mut simulator, &parse_script("down 3\nmove right 0.3\nmove left 0.5\nup").unwrap());
+        let executed = simulator.take_executed();
+
+        let phases = executed.iter().map(|action| env(action, "GEST_PHASE")).collect::<Vec<_>>();
+        assert_eq!(phases.first(), Some(&"begin"));
+        assert_eq!(phases.last(), Some(&"end"));
+        assert!(phases[1..phases.len() - 1].iter().all(|phase| *phase == "update"));
+        let distance = env(executed.last().unwrap(), "GEST_DISTANCE").parse::<f32>().unwrap();
+        assert!(distance < 0.0, "{}", distance);
+        assert!(executed.iter().any(|action| env(action, "GEST_DELTA").starts_with('-')));
+    }
+
+    #[test]
+    fn parse_script_steps() {
+        let steps = parse_script("# comment\n\ndown 2 at 0.2 50%\nmove by 0.1 -0.1 in 1s\nrotate 30 ccw\nwait 50ms\nwindow Firefox Nightly\nexpect Next tab\nexpect none").unwrap();
+        assert_eq!(steps, [
+            ScriptStep::Down { fingers: 2, x: 0.2, y: 0.5 },
+            ScriptStep::Move { dx: 0.1, dy: -0.1, duration: Duration::from_secs(1) },
+            ScriptStep::Rotate { angle: -30.0, duration: DEFAULT_MOVE_DURATION },
+            ScriptStep::Wait(Duration::from_millis(50)),
+            ScriptStep::Window { class: "Firefox Nightly".to_string() },
+            ScriptStep::Expect(Some("Next tab".to_string())),
+            ScriptStep::Expect(None),
+        ]);
+    }
+
+    #[test]
+    fn parse_script_errors_name_the_line() {
+        assert_eq!(parse_script("down 3\nmove sideways 0.3").unwrap_err(), "Line 2: Unknown direction: 'sideways'");
+        assert_eq!(parse_script("down 0").unwrap_err(), "Line 1: Invalid number of fingers: '0'");
+        assert!(parse_script("jump").is_err());
+    }
+
+    #[test]
+    fn run_script_reports_failures() {
+        let failures = run_script(&mut simulator(CONFIG), &parse_script("down 3\nmove left 0.3\nup\nexpect Tap\ndown 3\nup").unwrap());
+        assert_eq!(failures, ["expected Tap, matched Swipe left", "unexpected Tap"]);
+    }
+}
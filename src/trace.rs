@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use evdev::EventType;
+use crate::gestures::MoveThresholdUnits;
+use crate::input::RawEvent;
+
+// A trace is a text file with the touchpad size, followed by one event per line:
+//   size <width> <height>
+//   <seconds>.<microseconds> <type> <code> <value>
+const HEADER: &str = "# gest input trace";
+
+/// Touchpad events read from a trace file
+pub struct Trace {
+    pub touchpad_size: MoveThresholdUnits,
+    pub events: Vec<RawEvent>,
+}
+
+impl Trace {
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut touchpad_size = None;
+        let mut events = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid_line = || format!("Invalid trace line {}: '{}'", index + 1, line);
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                ["size", width, height] => {
+                    let (Ok(x), Ok(y)) = (width.parse(), height.parse()) else {
+                        return Err(invalid_line().into());
+                    };
+                    touchpad_size = Some(MoveThresholdUnits { x, y });
+                }
+                [time, event_type, code, value] => {
+                    let time = parse_time(time).ok_or_else(invalid_line)?;
+                    let (Ok(event_type), Ok(code), Ok(value)) = (event_type.parse(), code.parse(), value.parse()) else {
+                        return Err(invalid_line().into());
+                    };
+                    events.push(RawEvent { time, event_type: EventType(event_type), code, value });
+                }
+                _ => return Err(invalid_line().into()),
+            }
+        }
+
+        let touchpad_size = touchpad_size.ok_or("Trace does not contain the touchpad size")?;
+        Ok(Trace { touchpad_size, events })
+    }
+}
+
+/// Parses `<seconds>.<fraction>`, the fraction is scaled to microseconds, e.g. `1.5` is 1.5 seconds
+fn parse_time(s: &str) -> Option<Duration> {
+    let (seconds, fraction) = s.split_once('.')?;
+    if fraction.is_empty() || fraction.len() > 6 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let microseconds = fraction.parse::<u64>().ok()? * 10u64.pow(6 - fraction.len() as u32);
+    Some(Duration::from_secs(seconds.parse().ok()?) + Duration::from_micros(microseconds))
+}
+
+/// Writes multitouch events of the touchpad to a trace file
+pub struct TraceWriter {
+    writer: BufWriter<File>,
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path, touchpad_size: &MoveThresholdUnits) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "size {} {}", touchpad_size.x, touchpad_size.y)?;
+        Ok(TraceWriter { writer })
+    }
+
+    pub fn write(&mut self, event: &RawEvent) -> std::io::Result<()> {
+        if !event.is_multitouch() {
+            return Ok(());
+        }
+
+        writeln!(self.writer, "{}.{:06} {} {} {}", event.time.as_secs(), event.time.subsec_micros(), event.event_type.0, event.code, event.value)?;
+        // Frames end with a synchronization event, keep the file complete up to the last one
+        if event.event_type == EventType::SYNCHRONIZATION {
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::AbsoluteAxisCode;
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("12.000345"), Some(Duration::from_micros(12_000_345)));
+        assert_eq!(parse_time("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_time("0.05"), Some(Duration::from_millis(50)));
+        assert_eq!(parse_time("1.0000001"), None);
+        assert_eq!(parse_time("1."), None);
+        assert_eq!(parse_time("1.+5"), None);
+        assert_eq!(parse_time("1"), None);
+    }
+
+    #[test]
+    fn written_trace_reads_back() {
+        let path = std::env::temp_dir().join(format!("gest-test-{}.trace", std::process::id()));
+        let events = [
+            RawEvent { time: Duration::from_micros(5_000_042), event_type: EventType::ABSOLUTE, code: AbsoluteAxisCode::ABS_MT_TRACKING_ID.0, value: 7 },
+            RawEvent { time: Duration::from_micros(5_000_042), event_type: EventType::ABSOLUTE, code: AbsoluteAxisCode::ABS_MT_POSITION_X.0, value: 1200 },
+            // Not multitouch, so not written
+            RawEvent { time: Duration::from_micros(5_000_042), event_type: EventType::KEY, code: 330, value: 1 },
+            RawEvent { time: Duration::from_micros(5_500_000), event_type: EventType::SYNCHRONIZATION, code: 0, value: 0 },
+            RawEvent { time: Duration::from_micros(5_512_000), event_type: EventType::ABSOLUTE, code: AbsoluteAxisCode::ABS_MT_TRACKING_ID.0, value: -1 },
+        ];
+
+        let mut writer = TraceWriter::create(&path, &MoveThresholdUnits { x: 1200, y: 800 }).unwrap();
+        for event in &events {
+            writer.write(event).unwrap();
+        }
+        drop(writer);
+        let trace = Trace::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        let trace = trace.unwrap();
+
+        assert_eq!((trace.touchpad_size.x, trace.touchpad_size.y), (1200, 800));
+        let expected = events.iter().filter(|event| event.is_multitouch()).collect::<Vec<_>>();
+        assert_eq!(trace.events.len(), expected.len());
+        for (read, written) in trace.events.iter().zip(expected) {
+            assert_eq!((read.time, read.event_type, read.code, read.value), (written.time, written.event_type, written.code, written.value));
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use crate::Window;
+use super::{reconnect, OnWindowChange, WindowSource};
+
+/// Events of Hyprland's event socket which affect the active window
+#[derive(Debug, PartialEq)]
+enum HyprlandEvent<'a> {
+    /// carries only the class and title of the newly active window
+    ActiveWindow { class: &'a str, title: &'a str },
+    ActiveWindowAddress(&'a str),
+    WindowTitle { address: &'a str, title: &'a str },
+    Fullscreen(bool),
+    FloatingMode { address: &'a str, floating: bool },
+    Workspace(&'a str),
+}
+
+/// Parses a line of the event socket like `activewindow>>kitty,~`, returns `None` for other events
+fn parse_event(line: &str) -> Option<HyprlandEvent<'_>> {
+    let (event, data) = line.trim_end_matches('\n').split_once(">>")?;
+    Some(match event {
+        // Classes can't contain commas but titles can
+        "activewindow" => {
+            let (class, title) = data.split_once(',').unwrap_or((data, ""));
+            HyprlandEvent::ActiveWindow { class, title }
+        }
+        "activewindowv2" => HyprlandEvent::ActiveWindowAddress(data),
+        "windowtitlev2" => {
+            let (address, title) = data.split_once(',').unwrap_or((data, ""));
+            HyprlandEvent::WindowTitle { address, title }
+        }
+        "fullscreen" => HyprlandEvent::Fullscreen(data == "1"),
+        "changefloatingmode" => {
+            let (address, floating) = data.split_once(',').unwrap_or((data, ""));
+            HyprlandEvent::FloatingMode { address, floating: floating == "1" }
+        }
+        "workspacev2" => HyprlandEvent::Workspace(data.split_once(',').map_or(data, |(_, name)| name)),
+        _ => return None,
+    })
+}
+
+/// Parses the reply to the `activewindow` request into the address and state of the window.
+/// The reply looks like `Window 55d0c8d0a3e0 -> title:` followed by tab-indented `key: value` lines.
+fn parse_active_window(reply: &str) -> (String, Window) {
+    let mut lines = reply.lines();
+    let address = lines.next()
+        .and_then(|line| line.strip_prefix("Window "))
+        .and_then(|line| line.split_once(' '))
+        .map(|(address, _)| address.to_string())
+        .unwrap_or_default();
+    let mut window = Window::default();
+    for line in lines {
+        let Some((key, value)) = line.trim_start().split_once(": ") else {
+            continue;
+        };
+        match key {
+            "class" => window.class = value.to_string(),
+            "title" => window.title = value.to_string(),
+            // e.g. `workspace: 3 (3)`, the name is in parentheses
+            "workspace" => window.workspace = value.split_once(" (").map(|(_, name)| name.trim_end_matches(')').to_string()),
+            "floating" => window.floating = value == "1",
+            // A bitmask of the fullscreen modes, 1 is maximized and 2 fullscreen
+            "fullscreen" => {
+                let mode = value.parse::<u32>().unwrap_or_default();
+                window.maximized = mode & 1 != 0;
+                window.fullscreen = mode & 2 != 0;
+            }
+            _ => {}
+        }
+    }
+
+    (address, window)
+}
+
+/// Tracks the active window through Hyprland's event socket
+pub struct HyprlandMonitor {
+    socket_dir: PathBuf,
+    events: BufReader<UnixStream>,
+    on_window_change: OnWindowChange,
+}
+
+impl HyprlandMonitor {
+    pub fn new(on_window_change: OnWindowChange) -> Result<Self, Box<dyn std::error::Error>> {
+        let socket_dir = Self::socket_dir()?;
+        let events = Self::connect_events(&socket_dir)?;
+        Ok(HyprlandMonitor { socket_dir, events, on_window_change })
+    }
+
+    fn socket_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")
+            .map_err(|_| "HYPRLAND_INSTANCE_SIGNATURE not set (is Hyprland running?)")?;
+
+        // Hyprland moved its sockets from /tmp/hypr to $XDG_RUNTIME_DIR/hypr in v0.40
+        if let Ok(xdg_runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+            let dir = Path::new(&xdg_runtime_dir).join("hypr").join(&signature);
+            if dir.exists() {
+                return Ok(dir);
+            }
+        }
+        Ok(Path::new("/tmp/hypr").join(signature))
+    }
+
+    fn connect_events(socket_dir: &Path) -> Result<BufReader<UnixStream>, Box<dyn std::error::Error>> {
+        let socket = UnixStream::connect(socket_dir.join(".socket2.sock"))
+            .map_err(|e| format!("Failed to connect to Hyprland event socket: {}", e))?;
+        Ok(BufReader::new(socket))
+    }
+
+    /// Queries the currently active window, returns its address and state
+    fn query_active_window(&self) -> std::io::Result<(String, Window)> {
+        let mut socket = UnixStream::connect(self.socket_dir.join(".socket.sock"))?;
+        socket.write_all(b"activewindow")?;
+        let mut reply = String::new();
+        socket.read_to_string(&mut reply)?;
+        Ok(parse_active_window(&reply))
+    }
+
+    fn run_events(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut active_address, mut window) = match self.query_active_window() {
+            Ok(active_window) => active_window,
+            Err(e) => {
+                log::warn!("Failed to query Hyprland active window: {}", e);
+                Default::default()
+            }
+        };
+        (self.on_window_change)(window.clone());
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.events.read_line(&mut line)? == 0 {
+                return Err("Hyprland event socket closed".into());
+            }
+
+            let Some(event) = parse_event(&line) else {
+                continue;
+            };
+
+            match event {
+                HyprlandEvent::ActiveWindow { class, title } => {
+                    // The event only carries the class and title, the rest of the state is queried
+                    window = match self.query_active_window() {
+                        Ok((address, active_window)) => {
+                            active_address = address;
+                            active_window
+                        }
+                        Err(e) => {
+                            log::debug!("Failed to query Hyprland active window: {}", e);
+                            Window { workspace: window.workspace.clone(), ..Default::default() }
+                        }
+                    };
+                    window.class = class.to_string();
+                    window.title = title.to_string();
+                    (self.on_window_change)(window.clone());
+                }
+                HyprlandEvent::ActiveWindowAddress(address) => {
+                    active_address = address.to_string();
+                }
+                HyprlandEvent::WindowTitle { address, title } => {
+                    if !active_address.is_empty() && address == active_address && title != window.title {
+                        window.title = title.to_string();
+                        (self.on_window_change)(window.clone());
+                    }
+                }
+                HyprlandEvent::Fullscreen(fullscreen) => {
+                    // The event doesn't tell maximized and fullscreen apart
+                    match self.query_active_window() {
+                        Ok((address, active_window)) => {
+                            active_address = address;
+                            window = active_window;
+                        }
+                        Err(e) => {
+                            log::debug!("Failed to query Hyprland active window: {}", e);
+                            window.fullscreen = fullscreen;
+                        }
+                    }
+                    (self.on_window_change)(window.clone());
+                }
+                HyprlandEvent::FloatingMode { address, floating } => {
+                    if address == active_address {
+                        window.floating = floating;
+                        (self.on_window_change)(window.clone());
+                    }
+                }
+                HyprlandEvent::Workspace(name) => {
+                    window.workspace = Some(name.to_string());
+                    (self.on_window_change)(window.clone());
+                }
+            }
+        }
+    }
+}
+
+impl WindowSource for HyprlandMonitor {
+    fn name(&self) -> &'static str {
+        "hyprland"
+    }
+
+    /// Reconnects with exponential backoff when the event socket is closed, e.g. when Hyprland reloads
+    fn run(&mut self) {
+        loop {
+            if let Err(e) = self.run_events() {
+                log::error!("Hyprland window monitor disconnected: {}", e);
+            }
+            (self.on_window_change)(Window::default());
+
+            let socket_dir = self.socket_dir.clone();
+            self.events = reconnect(self.name(), || Self::connect_events(&socket_dir));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reply of Hyprland 0.41 to `activewindow`
+    const ACTIVE_WINDOW_REPLY: &str = "Window 55d0c8d0a3e0 -> ~/src/gest: nvim:
+\tmapped: 1
+\thidden: 0
+\tat: 10,50
+\tsize: 1900,1020
+\tworkspace: 3 (code)
+\tfloating: 0
+\tpseudo: 0
+\tmonitor: 0
+\tclass: kitty
+\ttitle: ~/src/gest: nvim
+\tinitialClass: kitty
+\tinitialTitle: kitty
+\tpid: 4242
+\txwayland: 0
+\tpinned: 0
+\tfullscreen: 0
+\tfullscreenmode: 0
+\tfakefullscreen: 0
+\tgrouped: 0
+\ttags:
+\tswallowing: 0
+\tfocusHistoryID: 0
+
+";
+
+    #[test]
+    fn active_window_reply() {
+        let (address, window) = parse_active_window(ACTIVE_WINDOW_REPLY);
+        assert_eq!(address, "55d0c8d0a3e0");
+        assert_eq!(window.class, "kitty");
+        assert_eq!(window.title, "~/src/gest: nvim");
+        assert_eq!(window.workspace.as_deref(), Some("code"));
+        assert!(!window.floating && !window.fullscreen && !window.maximized);
+    }
+
+    /// Reply of Hyprland 0.45 to `activewindow` for a maximized window
+    const MAXIMIZED_WINDOW_REPLY: &str = "Window 5a1e3b7c9d20 -> Mozilla Firefox:
+\tmapped: 1
+\thidden: 0
+\tat: 0,30
+\tsize: 2560,1410
+\tworkspace: 2 (2)
+\tfloating: 1
+\tpseudo: 0
+\tmonitor: 0
+\tclass: firefox
+\ttitle: Mozilla Firefox
+\tinitialClass: firefox
+\tinitialTitle: Mozilla Firefox
+\tpid: 1337
+\txwayland: 0
+\tpinned: 0
+\tfullscreen: 1
+\tfullscreenClient: 0
+\tgrouped: 0
+\ttags:
+\tswallowing: 0
+\tfocusHistoryID: 0
+\tinhibitingIdle: 0
+
+";
+
+    #[test]
+    fn fullscreen_modes() {
+        let (address, window) = parse_active_window(MAXIMIZED_WINDOW_REPLY);
+        assert_eq!(address, "5a1e3b7c9d20");
+        assert_eq!(window.workspace.as_deref(), Some("2"));
+        assert!(window.maximized && !window.fullscreen && window.floating);
+
+        let reply = MAXIMIZED_WINDOW_REPLY.replace("\tfullscreen: 1\n", "\tfullscreen: 2\n");
+        let (_, window) = parse_active_window(&reply);
+        assert!(!window.maximized && window.fullscreen);
+    }
+
+    #[test]
+    fn active_window_reply_without_window() {
+        let (address, window) = parse_active_window("Invalid\n");
+        assert_eq!(address, "");
+        assert_eq!(window.class, "");
+        assert_eq!(window.workspace, None);
+    }
+
+    #[test]
+    fn active_window_event() {
+        assert_eq!(parse_event("activewindow>>firefox,Hello, world\n"), Some(HyprlandEvent::ActiveWindow { class: "firefox", title: "Hello, world" }));
+        assert_eq!(parse_event("activewindow>>,\n"), Some(HyprlandEvent::ActiveWindow { class: "", title: "" }));
+        assert_eq!(parse_event("activewindowv2>>55d0c8d0a3e0\n"), Some(HyprlandEvent::ActiveWindowAddress("55d0c8d0a3e0")));
+    }
+
+    #[test]
+    fn window_title_event() {
+        assert_eq!(
+            parse_event("windowtitlev2>>55d0c8d0a3e0,a, b>>c\n"),
+            Some(HyprlandEvent::WindowTitle { address: "55d0c8d0a3e0", title: "a, b>>c" })
+        );
+    }
+
+    #[test]
+    fn other_events() {
+        assert_eq!(parse_event("changefloatingmode>>55d0c8d0a3e0,1\n"), Some(HyprlandEvent::FloatingMode { address: "55d0c8d0a3e0", floating: true }));
+        assert_eq!(parse_event("workspacev2>>2,web\n"), Some(HyprlandEvent::Workspace("web")));
+        assert_eq!(parse_event("openwindow>>55d0c8d0a3e0,2,kitty,kitty\n"), None);
+        assert_eq!(parse_event("garbage\n"), None);
+    }
+}
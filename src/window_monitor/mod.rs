@@ -0,0 +1,133 @@
+mod hyprland;
+mod sway;
+mod wayland;
+mod wlroots;
+mod x11;
+
+use std::sync::Arc;
+use std::time::Duration;
+use arc_swap::ArcSwap;
+use crate::Window;
+
+pub use hyprland::HyprlandMonitor;
+pub use sway::SwayMonitor;
+pub use wlroots::WlrootsMonitor;
+pub use x11::X11Monitor;
+
+pub type OnWindowChange = Arc<dyn Fn(Window) + Send + Sync>;
+
+/// Delays between reconnection attempts after the compositor went away
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// Provides the active window, which application gestures and `when` conditions are matched against
+pub trait WindowContext: Send + Sync {
+    fn active_window(&self) -> Arc<Window>;
+}
+
+/// The active window as last reported by a window source
+impl WindowContext for ArcSwap<Window> {
+    fn active_window(&self) -> Arc<Window> {
+        self.load_full()
+    }
+}
+
+/// A source of active window changes
+pub trait WindowSource: Send {
+    fn name(&self) -> &'static str;
+
+    /// Reports active window changes until the source fails or is closed
+    fn run(&mut self);
+}
+
+/// Which compositor interface is used to track the active window
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMonitorKind {
+    Wlroots,
+    Hyprland,
+    #[serde(alias = "i3")]
+    Sway,
+    X11,
+    /// disables active window tracking
+    None,
+}
+
+impl WindowMonitorKind {
+    /// Probing order when no window monitor is configured, compositor-specific sockets come before generic protocols
+    pub const DEFAULT_ORDER: [WindowMonitorKind; 4] = [
+        WindowMonitorKind::Hyprland,
+        WindowMonitorKind::Sway,
+        WindowMonitorKind::Wlroots,
+        WindowMonitorKind::X11,
+    ];
+
+    /// The default probing order for the current session. X11 is skipped in Wayland sessions
+    /// because through Xwayland it would only see X11 windows and miss every native one.
+    pub fn default_order() -> Vec<WindowMonitorKind> {
+        Self::default_order_for(std::env::var_os("WAYLAND_DISPLAY").is_some())
+    }
+
+    fn default_order_for(wayland: bool) -> Vec<WindowMonitorKind> {
+        Self::DEFAULT_ORDER.into_iter()
+            .filter(|kind| !(wayland && *kind == WindowMonitorKind::X11))
+            .collect()
+    }
+
+    fn connect(&self, on_window_change: OnWindowChange) -> Result<Box<dyn WindowSource>, Box<dyn std::error::Error>> {
+        Ok(match self {
+            WindowMonitorKind::Wlroots => Box::new(WlrootsMonitor::new(on_window_change)?),
+            WindowMonitorKind::Hyprland => Box::new(HyprlandMonitor::new(on_window_change)?),
+            WindowMonitorKind::Sway => Box::new(SwayMonitor::new(on_window_change)?),
+            WindowMonitorKind::X11 => Box::new(X11Monitor::new(on_window_change)?),
+            WindowMonitorKind::None => return Err("Active window tracking is disabled".into()),
+        })
+    }
+}
+
+/// Connects to the first available window source, in the given order
+pub fn probe(kinds: &[WindowMonitorKind], on_window_change: OnWindowChange) -> Option<Box<dyn WindowSource>> {
+    for kind in kinds {
+        if *kind == WindowMonitorKind::None {
+            return None;
+        }
+
+        match kind.connect(on_window_change.clone()) {
+            Ok(source) => return Some(source),
+            Err(e) => log::debug!("Window monitor {:?} is not available: {}", kind, e),
+        }
+    }
+    None
+}
+
+/// Calls `connect` with exponential backoff until it succeeds, e.g. while the compositor restarts
+fn reconnect<T>(name: &str, mut connect: impl FnMut() -> Result<T, Box<dyn std::error::Error>>) -> T {
+    let mut backoff = RECONNECT_BACKOFF_MIN;
+    loop {
+        std::thread::sleep(backoff);
+        match connect() {
+            Ok(connection) => {
+                log::info!("{} window monitor reconnected", name);
+                return connection;
+            }
+            Err(e) => {
+                log::debug!("Failed to reconnect {} window monitor: {}", name, e);
+                backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_order_skips_x11_on_wayland() {
+        assert_eq!(WindowMonitorKind::default_order_for(false), WindowMonitorKind::DEFAULT_ORDER);
+        assert_eq!(
+            WindowMonitorKind::default_order_for(true),
+            [WindowMonitorKind::Hyprland, WindowMonitorKind::Sway, WindowMonitorKind::Wlroots]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use crate::Window;
+use super::{OnWindowChange, WindowSource};
+
+const I3_IPC_MAGIC: &[u8] = b"i3-ipc";
+const I3_IPC_SUBSCRIBE: u32 = 2;
+const I3_IPC_GET_TREE: u32 = 4;
+const I3_IPC_EVENT_WORKSPACE: u32 = 0x80000000;
+const I3_IPC_EVENT_WINDOW: u32 = 0x80000003;
+
+/// Tracks the active window through the sway/i3 IPC socket
+pub struct SwayMonitor {
+    socket: UnixStream,
+    on_window_change: OnWindowChange,
+}
+
+impl SwayMonitor {
+    pub fn new(on_window_change: OnWindowChange) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = std::env::var("SWAYSOCK")
+            .or_else(|_| std::env::var("I3SOCK"))
+            .map_err(|_| "Neither SWAYSOCK nor I3SOCK is set (is sway or i3 running?)")?;
+        let socket = UnixStream::connect(&path)
+            .map_err(|e| format!("Failed to connect to IPC socket {}: {}", path, e))?;
+        Ok(SwayMonitor { socket, on_window_change })
+    }
+
+    fn send_message(&mut self, message_type: u32, payload: &[u8]) -> std::io::Result<()> {
+        let mut message = I3_IPC_MAGIC.to_vec();
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload);
+        self.socket.write_all(&message)
+    }
+
+    fn receive_message(&mut self) -> Result<(u32, serde_json::Value), Box<dyn std::error::Error>> {
+        let mut header = [0u8; 14];
+        self.socket.read_exact(&mut header)?;
+        if &header[..6] != I3_IPC_MAGIC {
+            return Err("Invalid IPC message magic".into());
+        }
+        let size = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+        let message_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+
+        let mut payload = vec![0u8; size];
+        self.socket.read_exact(&mut payload)?;
+
+        Ok((message_type, serde_json::from_slice(&payload)?))
+    }
+
+    /// Builds a window from a container node, app_id is set for Wayland windows and window_properties for X11 ones
+    fn window_from_container(container: &serde_json::Value, workspace: &Option<String>) -> Window {
+        let class = container["app_id"].as_str()
+            .or_else(|| container["window_properties"]["class"].as_str())
+            .unwrap_or_default();
+        // sway marks floating containers by type, i3 with the floating field
+        let floating = container["type"] == "floating_con"
+            || container["floating"].as_str().is_some_and(|floating| floating.ends_with("_on"));
+
+        Window {
+            class: class.to_string(),
+            title: container["name"].as_str().unwrap_or_default().to_string(),
+            workspace: workspace.clone(),
+            fullscreen: container["fullscreen_mode"].as_u64().is_some_and(|mode| mode > 0),
+            floating,
+            ..Default::default()
+        }
+    }
+
+    /// Finds the focused container in the tree, along with the name of its workspace
+    fn find_focused<'a>(node: &'a serde_json::Value, workspace: Option<&'a str>) -> Option<(&'a serde_json::Value, Option<&'a str>)> {
+        let workspace = if node["type"] == "workspace" { node["name"].as_str() } else { workspace };
+        if node["focused"] == true {
+            return Some((node, workspace));
+        }
+
+        ["nodes", "floating_nodes"].iter()
+            .filter_map(|key| node[key].as_array())
+            .flatten()
+            .find_map(|child| Self::find_focused(child, workspace))
+    }
+
+    fn run_ipc(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut workspace = None;
+        let mut focused_id = None;
+
+        self.send_message(I3_IPC_GET_TREE, &[])?;
+        let (_, tree) = self.receive_message()?;
+        if let Some((focused, focused_workspace)) = Self::find_focused(&tree, None) {
+            workspace = focused_workspace.map(str::to_string);
+            if focused["type"] == "con" || focused["type"] == "floating_con" {
+                focused_id = focused["id"].as_u64();
+                (self.on_window_change)(Self::window_from_container(focused, &workspace));
+            } else {
+                (self.on_window_change)(Window { workspace: workspace.clone(), ..Default::default() });
+            }
+        }
+
+        self.send_message(I3_IPC_SUBSCRIBE, br#"["window", "workspace"]"#)?;
+
+        loop {
+            let (message_type, event) = self.receive_message()?;
+            let change = event["change"].as_str().unwrap_or_default();
+
+            match message_type {
+                I3_IPC_EVENT_WORKSPACE if change == "focus" => {
+                    workspace = event["current"]["name"].as_str().map(str::to_string);
+                    // A window focus event follows unless the workspace is empty
+                    focused_id = None;
+                    (self.on_window_change)(Window { workspace: workspace.clone(), ..Default::default() });
+                }
+                I3_IPC_EVENT_WINDOW => {
+                    let container = &event["container"];
+                    let id = container["id"].as_u64();
+                    match change {
+                        "focus" => {
+                            focused_id = id;
+                            (self.on_window_change)(Self::window_from_container(container, &workspace));
+                        }
+                        "title" | "fullscreen_mode" | "floating" if id.is_some() && id == focused_id => {
+                            (self.on_window_change)(Self::window_from_container(container, &workspace));
+                        }
+                        "close" if id.is_some() && id == focused_id => {
+                            focused_id = None;
+                            (self.on_window_change)(Window { workspace: workspace.clone(), ..Default::default() });
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl WindowSource for SwayMonitor {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn run(&mut self) {
+        if let Err(e) = self.run_ipc() {
+            log::error!("sway/i3 window monitor stopped: {}", e);
+        }
+    }
+}
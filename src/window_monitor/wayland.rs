@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+const DISPLAY_ID: u32 = 1;
+// wl_display requests and events
+const DISPLAY_SYNC: u32 = 0;
+const DISPLAY_GET_REGISTRY: u32 = 1;
+const DISPLAY_ERROR: u32 = 0;
+const DISPLAY_DELETE_ID: u32 = 1;
+// wl_registry requests and events
+const REGISTRY_BIND: u32 = 0;
+const REGISTRY_GLOBAL: u32 = 0;
+// wl_callback events
+const CALLBACK_DONE: u32 = 0;
+
+/// Ids of objects created by the compositor start here
+const SERVER_ID_START: u32 = 0xff000000;
+
+pub struct Message {
+    pub object_id: u32,
+    pub opcode: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    pub fn args(&self) -> Args<'_> {
+        Args { payload: &self.payload, position: 0 }
+    }
+}
+
+/// Reads the arguments of a message in order
+pub struct Args<'a> {
+    payload: &'a [u8],
+    position: usize,
+}
+
+impl Args<'_> {
+    pub fn u32(&mut self) -> Option<u32> {
+        let bytes = self.payload.get(self.position..self.position + 4)?;
+        self.position += 4;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    /// Reads a length-prefixed array, padded to 32 bits
+    pub fn array(&mut self) -> Option<&[u8]> {
+        let len = self.u32()? as usize;
+        let bytes = self.payload.get(self.position..self.position + len)?;
+        self.position += len.div_ceil(4) * 4;
+        Some(bytes)
+    }
+
+    pub fn u32_array(&mut self) -> Option<Vec<u32>> {
+        let bytes = self.array()?;
+        Some(bytes.chunks_exact(4).map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect())
+    }
+
+    /// Reads a null-terminated string, which is an array including the null byte
+    pub fn string(&mut self) -> Option<String> {
+        let bytes = self.array()?;
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        Some(String::from_utf8_lossy(bytes).to_string())
+    }
+}
+
+/// Builds the arguments of a request
+#[derive(Default)]
+pub struct Payload(Vec<u8>);
+
+impl Payload {
+    pub fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn string(mut self, value: &str) -> Self {
+        self = self.u32(value.len() as u32 + 1);
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.push(0);
+        while !self.0.len().is_multiple_of(4) {
+            self.0.push(0);
+        }
+        self
+    }
+}
+
+/// Minimal Wayland client speaking the wire protocol, tracking the interfaces of its objects
+pub struct Wayland {
+    socket: UnixStream,
+    objects: HashMap<u32, &'static str>,
+    free_ids: Vec<u32>,
+    next_id: u32,
+    registry: u32,
+}
+
+impl Wayland {
+    /// Connects to the compositor of `WAYLAND_DISPLAY`
+    pub fn connect() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut path = std::env::var("WAYLAND_DISPLAY").map_err(|_| "WAYLAND_DISPLAY not set (is wayland running?)")?;
+        if !path.starts_with('/') {
+            let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR").map_err(|_| "XDG_RUNTIME_DIR not set")?;
+            path = format!("{}/{}", xdg_runtime_dir, path);
+        }
+
+        let socket = UnixStream::connect(&path).map_err(|e| format!("Failed to connect to {}: {}", path, e))?;
+        Self::from_stream(socket)
+    }
+
+    /// Uses an already connected socket, e.g. one end of a socket pair with a mock compositor on the other
+    pub fn from_stream(socket: UnixStream) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut wayland = Wayland {
+            socket,
+            objects: HashMap::from([(DISPLAY_ID, "wl_display")]),
+            free_ids: Vec::new(),
+            next_id: DISPLAY_ID + 1,
+            registry: 0,
+        };
+        wayland.registry = wayland.new_id("wl_registry");
+        wayland.send_message(DISPLAY_ID, DISPLAY_GET_REGISTRY, Payload::default().u32(wayland.registry))?;
+        Ok(wayland)
+    }
+
+    /// Allocates an id for a new client object, reusing ids released by the compositor
+    pub fn new_id(&mut self, interface: &'static str) -> u32 {
+        let id = self.free_ids.pop().unwrap_or_else(|| {
+            self.next_id += 1;
+            self.next_id - 1
+        });
+        self.objects.insert(id, interface);
+        id
+    }
+
+    /// Registers an object created by the compositor through a `new_id` event argument
+    pub fn add_server_object(&mut self, id: u32, interface: &'static str) -> Result<(), Box<dyn std::error::Error>> {
+        if id < SERVER_ID_START {
+            return Err(format!("Invalid server object id {}", id).into());
+        }
+        self.objects.insert(id, interface);
+        Ok(())
+    }
+
+    /// Sends the destructor request of an object, client ids are released once the compositor acknowledges it
+    pub fn destroy(&mut self, id: u32, opcode: u32) -> std::io::Result<()> {
+        self.send_message(id, opcode, Payload::default())?;
+        if id >= SERVER_ID_START {
+            self.objects.remove(&id);
+        }
+        Ok(())
+    }
+
+    pub fn interface(&self, id: u32) -> Option<&'static str> {
+        self.objects.get(&id).copied()
+    }
+
+    /// Binds the global with the given interface, at the highest version supported by both sides
+    pub fn bind(&mut self, interface: &'static str, max_version: u32) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+        // The callback is done once all globals have been announced
+        let callback = self.new_id("wl_callback");
+        self.send_message(DISPLAY_ID, DISPLAY_SYNC, Payload::default().u32(callback))?;
+
+        loop {
+            let message = self.receive_message()?;
+            if message.object_id == self.registry && message.opcode == REGISTRY_GLOBAL {
+                let mut args = message.args();
+                let (Some(name), Some(global_interface), Some(version)) = (args.u32(), args.string(), args.u32()) else {
+                    return Err("Malformed wl_registry.global event".into());
+                };
+
+                if global_interface == interface {
+                    let version = version.min(max_version);
+                    let id = self.new_id(interface);
+                    let payload = Payload::default().u32(name).string(interface).u32(version).u32(id);
+                    self.send_message(self.registry, REGISTRY_BIND, payload)?;
+                    return Ok((id, version));
+                }
+            }
+
+            if message.object_id == callback && message.opcode == CALLBACK_DONE {
+                return Err(format!("Compositor does not support {}", interface).into());
+            }
+        }
+    }
+
+    pub fn send_message(&mut self, object_id: u32, opcode: u32, payload: Payload) -> std::io::Result<()> {
+        let size = payload.0.len() as u32 + 8;
+        let full_opcode = opcode | (size << 16);
+        let mut message = object_id.to_le_bytes().to_vec();
+        message.extend_from_slice(&full_opcode.to_le_bytes());
+        message.extend_from_slice(&payload.0);
+        self.socket.write_all(&message)
+    }
+
+    /// Receives the next event, wl_display events are handled here
+    pub fn receive_message(&mut self) -> Result<Message, Box<dyn std::error::Error>> {
+        loop {
+            let message = self.read_message()?;
+            if message.object_id != DISPLAY_ID {
+                return Ok(message);
+            }
+
+            let mut args = message.args();
+            match message.opcode {
+                DISPLAY_ERROR => {
+                    let object_id = args.u32().unwrap_or_default();
+                    let code = args.u32().unwrap_or_default();
+                    let error = args.string().unwrap_or_default();
+                    let interface = self.interface(object_id).unwrap_or("unknown");
+                    return Err(format!("Compositor error on {}@{} (code {}): {}", interface, object_id, code, error).into());
+                }
+                DISPLAY_DELETE_ID => {
+                    if let Some(id) = args.u32() && self.objects.remove(&id).is_some() && id < SERVER_ID_START {
+                        self.free_ids.push(id);
+                    }
+                }
+                _ => log::debug!("Unknown wl_display event {}", message.opcode),
+            }
+        }
+    }
+
+    fn read_message(&mut self) -> std::io::Result<Message> {
+        let mut header = [0u8; 8];
+        self.socket.read_exact(&mut header)?;
+        let object_id = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let evcode = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let size = (evcode >> 16) as usize;
+        let opcode = evcode & 0xFFFF;
+
+        if size < 8 || !size.is_multiple_of(4) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid message size {}", size)));
+        }
+        let mut payload = vec![0u8; size - 8];
+        self.socket.read_exact(&mut payload)?;
+
+        Ok(Message { object_id, opcode, payload })
+    }
+}
+
+
+#[cfg(test)]
+pub(super) mod mock {
+    use super::*;
+
+    /// Encodes an event as the compositor would send it
+    pub fn event(object_id: u32, opcode: u32, payload: Payload) -> Vec<u8> {
+        let size = payload.0.len() as u32 + 8;
+        let mut bytes = object_id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(opcode | (size << 16)).to_le_bytes());
+        bytes.extend_from_slice(&payload.0);
+        bytes
+    }
+
+    /// Reads the next request of the client, as the compositor would
+    pub fn read_request(socket: &mut UnixStream) -> Message {
+        let mut header = [0u8; 8];
+        socket.read_exact(&mut header).unwrap();
+        let object_id = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let opcode = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut payload = vec![0u8; (opcode >> 16) as usize - 8];
+        socket.read_exact(&mut payload).unwrap();
+        Message { object_id, opcode: opcode & 0xffff, payload }
+    }
+
+    /// Reads the get_registry request a client sends when connecting, returns the registry id
+    pub fn accept(compositor: &mut UnixStream) -> u32 {
+        let request = read_request(compositor);
+        assert_eq!((request.object_id, request.opcode), (DISPLAY_ID, DISPLAY_GET_REGISTRY));
+        request.args().u32().unwrap()
+    }
+
+    /// Client connected to a mock compositor, whose get_registry request was already read
+    pub fn connect() -> (Wayland, UnixStream) {
+        let (client, mut compositor) = UnixStream::pair().unwrap();
+        let wayland = Wayland::from_stream(client).unwrap();
+        assert_eq!(accept(&mut compositor), wayland.registry);
+        (wayland, compositor)
+    }
+
+    pub fn global(registry: u32, name: u32, interface: &str, version: u32) -> Vec<u8> {
+        event(registry, REGISTRY_GLOBAL, Payload::default().u32(name).string(interface).u32(version))
+    }
+
+    impl Payload {
+        pub fn u32_array(mut self, values: &[u32]) -> Self {
+            self = self.u32(values.len() as u32 * 4);
+            for value in values {
+                self = self.u32(*value);
+            }
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::mock::*;
+    use std::time::Duration;
+
+    #[test]
+    fn bind_clamps_version_to_the_client_maximum() {
+        let (mut wayland, mut compositor) = connect();
+        compositor.write_all(&global(wayland.registry, 1, "wl_compositor", 6)).unwrap();
+        compositor.write_all(&global(wayland.registry, 7, "zwlr_foreign_toplevel_manager_v1", 3)).unwrap();
+
+        let (id, version) = wayland.bind("zwlr_foreign_toplevel_manager_v1", 2).unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(wayland.interface(id), Some("zwlr_foreign_toplevel_manager_v1"));
+
+        let sync = read_request(&mut compositor);
+        assert_eq!((sync.object_id, sync.opcode), (DISPLAY_ID, DISPLAY_SYNC));
+        let bind = read_request(&mut compositor);
+        assert_eq!((bind.object_id, bind.opcode), (wayland.registry, REGISTRY_BIND));
+        let mut args = bind.args();
+        assert_eq!(args.u32(), Some(7));
+        assert_eq!(args.string().as_deref(), Some("zwlr_foreign_toplevel_manager_v1"));
+        assert_eq!(args.u32(), Some(2));
+        assert_eq!(args.u32(), Some(id));
+    }
+
+    #[test]
+    fn bind_uses_the_compositor_version_if_lower() {
+        let (mut wayland, mut compositor) = connect();
+        compositor.write_all(&global(wayland.registry, 3, "ext_foreign_toplevel_list_v1", 1)).unwrap();
+
+        let (_, version) = wayland.bind("ext_foreign_toplevel_list_v1", 3).unwrap();
+        assert_eq!(version, 1);
+    }
+
+    #[test]
+    fn bind_fails_once_all_globals_were_announced() {
+        let (mut wayland, mut compositor) = connect();
+        compositor.write_all(&global(wayland.registry, 1, "wl_compositor", 6)).unwrap();
+        // The sync callback will be the next id
+        let callback = wayland.next_id;
+        compositor.write_all(&event(callback, CALLBACK_DONE, Payload::default().u32(0))).unwrap();
+
+        let error = wayland.bind("zwlr_foreign_toplevel_manager_v1", 3).unwrap_err();
+        assert!(error.to_string().contains("does not support zwlr_foreign_toplevel_manager_v1"), "{}", error);
+    }
+
+    #[test]
+    fn display_error_names_the_object() {
+        let (mut wayland, mut compositor) = connect();
+        let payload = Payload::default().u32(wayland.registry).u32(1).string("invalid global");
+        compositor.write_all(&event(DISPLAY_ID, DISPLAY_ERROR, payload)).unwrap();
+
+        let error = wayland.receive_message().err().unwrap().to_string();
+        assert_eq!(error, format!("Compositor error on wl_registry@{} (code 1): invalid global", wayland.registry));
+    }
+
+    #[test]
+    fn deleted_ids_are_reused() {
+        let (mut wayland, mut compositor) = connect();
+        let callback = wayland.new_id("wl_callback");
+        compositor.write_all(&event(DISPLAY_ID, DISPLAY_DELETE_ID, Payload::default().u32(callback))).unwrap();
+        // delete_id is handled while waiting for the next event of another object
+        compositor.write_all(&event(wayland.registry, REGISTRY_GLOBAL, Payload::default())).unwrap();
+
+        let message = wayland.receive_message().unwrap();
+        assert_eq!(message.object_id, wayland.registry);
+        assert_eq!(wayland.interface(callback), None);
+        assert_eq!(wayland.new_id("zwlr_foreign_toplevel_manager_v1"), callback);
+        assert_eq!(wayland.interface(callback), Some("zwlr_foreign_toplevel_manager_v1"));
+    }
+
+    #[test]
+    fn deleted_server_ids_are_not_reused() {
+        let (mut wayland, mut compositor) = connect();
+        wayland.add_server_object(SERVER_ID_START, "zwlr_foreign_toplevel_handle_v1").unwrap();
+        compositor.write_all(&event(DISPLAY_ID, DISPLAY_DELETE_ID, Payload::default().u32(SERVER_ID_START))).unwrap();
+        compositor.write_all(&event(wayland.registry, REGISTRY_GLOBAL, Payload::default())).unwrap();
+
+        wayland.receive_message().unwrap();
+        assert_eq!(wayland.interface(SERVER_ID_START), None);
+        assert!(wayland.new_id("wl_callback") < SERVER_ID_START);
+        assert!(wayland.add_server_object(5, "zwlr_foreign_toplevel_handle_v1").is_err());
+    }
+
+    #[test]
+    fn messages_split_across_reads() {
+        let (mut wayland, mut compositor) = connect();
+        let bytes = global(wayland.registry, 9, "wl_seat", 7);
+        // Deliver the message a few bytes at a time, so the client has to wait for the rest
+        let writer = std::thread::spawn(move || {
+            for chunk in bytes.chunks(3) {
+                compositor.write_all(chunk).unwrap();
+                std::thread::sleep(Duration::from_millis(2));
+            }
+            compositor
+        });
+
+        let message = wayland.receive_message().unwrap();
+        writer.join().unwrap();
+        assert_eq!((message.object_id, message.opcode), (wayland.registry, REGISTRY_GLOBAL));
+        let mut args = message.args();
+        assert_eq!(args.u32(), Some(9));
+        assert_eq!(args.string().as_deref(), Some("wl_seat"));
+        assert_eq!(args.u32(), Some(7));
+    }
+
+    #[test]
+    fn invalid_message_size_is_an_error() {
+        let (mut wayland, mut compositor) = connect();
+        let mut bytes = wayland.registry.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(6u32 << 16).to_le_bytes());
+        compositor.write_all(&bytes).unwrap();
+
+        assert!(wayland.receive_message().is_err());
+    }
+
+    #[test]
+    fn closed_connection_is_an_error() {
+        let (mut wayland, compositor) = connect();
+        drop(compositor);
+
+        assert!(wayland.receive_message().is_err());
+    }
+}
@@ -0,0 +1,314 @@
+// https://github.com/rvaiya/keyd/blob/master/scripts/keyd-application-mapper <3
+
+use std::collections::HashMap;
+use crate::Window;
+use super::{reconnect, OnWindowChange, WindowSource};
+use super::wayland::Wayland;
+
+const WLROOTS_INTERFACE_NAME: &str = "zwlr_foreign_toplevel_manager_v1";
+const WLROOTS_INTERFACE_VERSION: u32 = 3;
+const TOPLEVEL_INTERFACE_NAME: &str = "zwlr_foreign_toplevel_handle_v1";
+// zwlr_foreign_toplevel_manager_v1 events
+const MANAGER_TOPLEVEL: u32 = 0;
+const MANAGER_FINISHED: u32 = 1;
+
+// zwlr_foreign_toplevel_handle_v1 events
+const TOPLEVEL_TITLE: u32 = 0;
+const TOPLEVEL_APP_ID: u32 = 1;
+const TOPLEVEL_STATE: u32 = 4;
+const TOPLEVEL_DONE: u32 = 5;
+const TOPLEVEL_CLOSED: u32 = 6;
+// zwlr_foreign_toplevel_handle_v1 requests
+const TOPLEVEL_DESTROY: u32 = 7;
+// zwlr_foreign_toplevel_handle_v1 states
+const TOPLEVEL_STATE_MAXIMIZED: u32 = 0;
+const TOPLEVEL_STATE_MINIMIZED: u32 = 1;
+const TOPLEVEL_STATE_ACTIVATED: u32 = 2;
+const TOPLEVEL_STATE_FULLSCREEN: u32 = 3;
+
+/// Toplevel properties, pending changes are applied on the `done` event
+#[derive(Default)]
+struct Toplevel {
+    title: Option<String>,
+    class_name: Option<String>,
+    states: Vec<u32>,
+    pending_title: Option<String>,
+    pending_class_name: Option<String>,
+    pending_states: Option<Vec<u32>>,
+}
+
+impl Toplevel {
+    fn window(&self) -> Window {
+        Window {
+            class: self.class_name.clone().unwrap_or_default(),
+            title: self.title.clone().unwrap_or_default(),
+            fullscreen: self.states.contains(&TOPLEVEL_STATE_FULLSCREEN),
+            maximized: self.states.contains(&TOPLEVEL_STATE_MAXIMIZED),
+            minimized: self.states.contains(&TOPLEVEL_STATE_MINIMIZED),
+            ..Default::default()
+        }
+    }
+
+    fn activated(&self) -> bool {
+        self.states.contains(&TOPLEVEL_STATE_ACTIVATED)
+    }
+}
+
+/// Opens a new connection to the compositor, called again to reconnect
+type Connect = Box<dyn FnMut() -> Result<Wayland, Box<dyn std::error::Error>> + Send>;
+
+pub struct WlrootsMonitor {
+    connect: Connect,
+    wayland: Wayland,
+    manager: u32,
+    on_window_change: OnWindowChange,
+}
+
+impl WlrootsMonitor {
+    pub fn new(on_window_change: OnWindowChange) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_connection(Wayland::connect, on_window_change)
+    }
+
+    /// Uses `connect` instead of `WAYLAND_DISPLAY` to reach the compositor, e.g. `Wayland::from_stream` with a mock compositor
+    pub(crate) fn with_connection(
+        connect: impl FnMut() -> Result<Wayland, Box<dyn std::error::Error>> + Send + 'static,
+        on_window_change: OnWindowChange,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut connect: Connect = Box::new(connect);
+        let (wayland, manager) = Self::bind(connect()?)?;
+        Ok(WlrootsMonitor { connect, wayland, manager, on_window_change })
+    }
+
+    fn bind(mut wayland: Wayland) -> Result<(Wayland, u32), Box<dyn std::error::Error>> {
+        let (manager, version) = wayland.bind(WLROOTS_INTERFACE_NAME, WLROOTS_INTERFACE_VERSION)?;
+        log::debug!("Bound {} version {}", WLROOTS_INTERFACE_NAME, version);
+        Ok((wayland, manager))
+    }
+
+    fn run_wayland(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut windows: HashMap<u32, Toplevel> = HashMap::new();
+        let mut active = None;
+
+        loop {
+            let message = self.wayland.receive_message()?;
+            let obj = message.object_id;
+            let mut args = message.args();
+
+            if obj == self.manager {
+                match message.opcode {
+                    MANAGER_TOPLEVEL => {
+                        let handle = args.u32().ok_or("Malformed toplevel event")?;
+                        self.wayland.add_server_object(handle, TOPLEVEL_INTERFACE_NAME)?;
+                        windows.insert(handle, Toplevel::default());
+                    }
+                    MANAGER_FINISHED => return Err("Foreign toplevel manager finished".into()),
+                    _ => {}
+                }
+                continue;
+            }
+
+            let Some(win) = windows.get_mut(&obj) else {
+                continue;
+            };
+
+            match message.opcode {
+                TOPLEVEL_TITLE => win.pending_title = args.string(),
+                TOPLEVEL_APP_ID => win.pending_class_name = args.string(),
+                TOPLEVEL_STATE => win.pending_states = Some(args.u32_array().unwrap_or_default()),
+                TOPLEVEL_DONE => {
+                    let mut changed = false;
+                    if let Some(title) = win.pending_title.take() {
+                        changed |= win.title.as_ref() != Some(&title);
+                        win.title = Some(title);
+                    }
+                    if let Some(class_name) = win.pending_class_name.take() {
+                        changed |= win.class_name.as_ref() != Some(&class_name);
+                        win.class_name = Some(class_name);
+                    }
+                    if let Some(states) = win.pending_states.take() {
+                        changed |= win.states != states;
+                        win.states = states;
+                    }
+
+                    if win.activated() && (active != Some(obj) || changed) {
+                        active = Some(obj);
+                        (self.on_window_change)(win.window());
+                    } else if !win.activated() && active == Some(obj) {
+                        active = None;
+                        (self.on_window_change)(Window::default());
+                    }
+                }
+                TOPLEVEL_CLOSED => {
+                    windows.remove(&obj);
+                    self.wayland.destroy(obj, TOPLEVEL_DESTROY)?;
+                    if active == Some(obj) {
+                        active = None;
+                        (self.on_window_change)(Window::default());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl WindowSource for WlrootsMonitor {
+    fn name(&self) -> &'static str {
+        "wlroots"
+    }
+
+    /// Reconnects with exponential backoff when the connection to the compositor is lost, e.g. when it restarts
+    fn run(&mut self) {
+        loop {
+            if let Err(e) = self.run_wayland() {
+                log::error!("wlroots window monitor disconnected: {}", e);
+            }
+            (self.on_window_change)(Window::default());
+
+            let name = self.name();
+            let connect = &mut self.connect;
+            (self.wayland, self.manager) = reconnect(name, || Self::bind(connect()?));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+    use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+    use std::time::Duration;
+    use super::super::wayland::{mock, Payload};
+
+    /// Longer than the first reconnection delay
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    /// Ids of toplevels announced by the mock compositor
+    const TOPLEVEL: u32 = 0xff000000;
+    const OTHER_TOPLEVEL: u32 = 0xff000001;
+
+    /// A monitor running in the background, which takes its connections from `clients`
+    struct Session {
+        clients: Sender<UnixStream>,
+        windows: Receiver<Window>,
+    }
+
+    impl Session {
+        fn start() -> Self {
+            let (clients, client_receiver) = std::sync::mpsc::channel::<UnixStream>();
+            let (window_sender, windows) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                let connect = move || Wayland::from_stream(client_receiver.recv()?);
+                let on_window_change: OnWindowChange = Arc::new(move |window| {
+                    let _ = window_sender.send(window);
+                });
+                if let Ok(mut monitor) = WlrootsMonitor::with_connection(connect, on_window_change) {
+                    monitor.run();
+                }
+            });
+            Session { clients, windows }
+        }
+
+        /// Hands the monitor a new connection and plays the compositor until it bound the toplevel manager
+        fn connect(&self) -> Compositor {
+            let (client, mut socket) = UnixStream::pair().unwrap();
+            self.clients.send(client).unwrap();
+            let registry = mock::accept(&mut socket);
+            socket.write_all(&mock::global(registry, 1, WLROOTS_INTERFACE_NAME, WLROOTS_INTERFACE_VERSION)).unwrap();
+
+            // sync, then bind with the name, interface, version and id of the manager
+            mock::read_request(&mut socket);
+            let bind = mock::read_request(&mut socket);
+            let mut args = bind.args();
+            args.u32();
+            args.string();
+            args.u32();
+            Compositor { socket, manager: args.u32().unwrap() }
+        }
+
+        fn next_window(&self) -> Window {
+            self.windows.recv_timeout(TIMEOUT).unwrap()
+        }
+
+        fn assert_no_window_change(&self) {
+            assert_eq!(self.windows.try_recv().err(), Some(TryRecvError::Empty));
+        }
+    }
+
+    /// Mock compositor on the other end of a connection of the monitor
+    struct Compositor {
+        socket: UnixStream,
+        manager: u32,
+    }
+
+    impl Compositor {
+        fn send(&mut self, object_id: u32, opcode: u32, payload: Payload) {
+            self.socket.write_all(&mock::event(object_id, opcode, payload)).unwrap();
+        }
+
+        /// Announces a toplevel with the given properties and applies them
+        fn announce(&mut self, toplevel: u32, title: &str, states: &[u32]) {
+            self.send(self.manager, MANAGER_TOPLEVEL, Payload::default().u32(toplevel));
+            self.send(toplevel, TOPLEVEL_APP_ID, Payload::default().string("kitty"));
+            self.send(toplevel, TOPLEVEL_TITLE, Payload::default().string(title));
+            self.send(toplevel, TOPLEVEL_STATE, Payload::default().u32_array(states));
+            self.send(toplevel, TOPLEVEL_DONE, Payload::default());
+        }
+
+        /// Closes the toplevel and waits for the client to destroy it, so all events sent before were handled
+        fn close(&mut self, toplevel: u32) {
+            self.send(toplevel, TOPLEVEL_CLOSED, Payload::default());
+            let request = mock::read_request(&mut self.socket);
+            assert_eq!((request.object_id, request.opcode), (toplevel, TOPLEVEL_DESTROY));
+        }
+    }
+
+    #[test]
+    fn title_change_of_active_toplevel_is_reported_on_done() {
+        let session = Session::start();
+        let mut compositor = session.connect();
+        compositor.announce(TOPLEVEL, "one", &[TOPLEVEL_STATE_ACTIVATED]);
+        let window = session.next_window();
+        assert_eq!((window.class.as_str(), window.title.as_str()), ("kitty", "one"));
+
+        compositor.send(TOPLEVEL, TOPLEVEL_TITLE, Payload::default().string("two"));
+        compositor.announce(OTHER_TOPLEVEL, "other", &[]);
+        compositor.close(OTHER_TOPLEVEL);
+        session.assert_no_window_change();
+
+        compositor.send(TOPLEVEL, TOPLEVEL_DONE, Payload::default());
+        let window = session.next_window();
+        assert_eq!((window.class.as_str(), window.title.as_str()), ("kitty", "two"));
+    }
+
+    #[test]
+    fn closing_active_toplevel_resets_the_window() {
+        let session = Session::start();
+        let mut compositor = session.connect();
+        compositor.announce(TOPLEVEL, "one", &[TOPLEVEL_STATE_ACTIVATED, TOPLEVEL_STATE_MAXIMIZED]);
+        assert!(session.next_window().maximized);
+
+        compositor.close(TOPLEVEL);
+        assert_eq!(session.next_window(), Window::default());
+        session.assert_no_window_change();
+    }
+
+    #[test]
+    fn dropped_connection_is_reconnected() {
+        let session = Session::start();
+        let mut compositor = session.connect();
+        compositor.announce(TOPLEVEL, "one", &[TOPLEVEL_STATE_ACTIVATED]);
+        assert_eq!(session.next_window().title, "one");
+
+        drop(compositor);
+        assert_eq!(session.next_window(), Window::default());
+
+        // The new connection starts from scratch, the compositor announces its toplevels again
+        let mut compositor = session.connect();
+        compositor.announce(TOPLEVEL, "one", &[TOPLEVEL_STATE_ACTIVATED, TOPLEVEL_STATE_FULLSCREEN]);
+        let window = session.next_window();
+        assert_eq!(window.title, "one");
+        assert!(window.fullscreen);
+    }
+}
@@ -0,0 +1,250 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::{Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask};
+use x11rb::rust_connection::RustConnection;
+use crate::Window;
+use super::{OnWindowChange, WindowSource};
+
+struct X11Atoms {
+    net_active_window: Atom,
+    net_wm_name: Atom,
+    net_wm_state: Atom,
+    net_wm_state_fullscreen: Atom,
+    net_wm_state_maximized_vert: Atom,
+    net_wm_state_maximized_horz: Atom,
+    net_wm_state_hidden: Atom,
+    utf8_string: Atom,
+}
+
+impl X11Atoms {
+    /// Sets the fullscreen, maximized and minimized state of a window from the atoms of its `_NET_WM_STATE` property
+    fn apply_state(&self, states: &[Atom], window: &mut Window) {
+        window.fullscreen = states.contains(&self.net_wm_state_fullscreen);
+        // Windows maximized in a single direction are not considered maximized
+        window.maximized = states.contains(&self.net_wm_state_maximized_vert) && states.contains(&self.net_wm_state_maximized_horz);
+        window.minimized = states.contains(&self.net_wm_state_hidden);
+    }
+}
+
+/// Class name of a `WM_CLASS` property, which holds the instance and class names each followed by a null byte.
+/// Falls back to the instance name for clients which only set one.
+fn parse_wm_class(value: &[u8]) -> String {
+    let mut names = value.split(|byte| *byte == 0).filter(|name| !name.is_empty());
+    let instance = names.next().unwrap_or_default();
+    let class = names.next().unwrap_or(instance);
+    String::from_utf8_lossy(class).to_string()
+}
+
+/// Atoms of a property in format 32, in the byte order of the connection
+fn parse_atom_list(format: u8, value: &[u8]) -> Vec<Atom> {
+    if format != 32 {
+        return Vec::new();
+    }
+    value.chunks_exact(4).map(|bytes| Atom::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect()
+}
+
+/// Tracks the active window through `_NET_ACTIVE_WINDOW` on the X11 root window
+pub struct X11Monitor {
+    connection: RustConnection,
+    root: u32,
+    atoms: X11Atoms,
+    on_window_change: OnWindowChange,
+}
+
+impl X11Monitor {
+    pub fn new(on_window_change: OnWindowChange) -> Result<Self, Box<dyn std::error::Error>> {
+        let (connection, screen_num) = x11rb::connect(None)?;
+        let root = connection.setup().roots[screen_num].root;
+
+        let intern = |name: &str| -> Result<Atom, Box<dyn std::error::Error>> {
+            Ok(connection.intern_atom(false, name.as_bytes())?.reply()?.atom)
+        };
+        let atoms = X11Atoms {
+            net_active_window: intern("_NET_ACTIVE_WINDOW")?,
+            net_wm_name: intern("_NET_WM_NAME")?,
+            net_wm_state: intern("_NET_WM_STATE")?,
+            net_wm_state_fullscreen: intern("_NET_WM_STATE_FULLSCREEN")?,
+            net_wm_state_maximized_vert: intern("_NET_WM_STATE_MAXIMIZED_VERT")?,
+            net_wm_state_maximized_horz: intern("_NET_WM_STATE_MAXIMIZED_HORZ")?,
+            net_wm_state_hidden: intern("_NET_WM_STATE_HIDDEN")?,
+            utf8_string: intern("UTF8_STRING")?,
+        };
+
+        let attributes = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+        connection.change_window_attributes(root, &attributes)?.check()?;
+
+        Ok(X11Monitor { connection, root, atoms, on_window_change })
+    }
+
+    fn active_window(&self) -> Result<Option<u32>, Box<dyn std::error::Error>> {
+        let reply = self.connection
+            .get_property(false, self.root, self.atoms.net_active_window, AtomEnum::WINDOW, 0, 1)?
+            .reply()?;
+        Ok(reply.value32().and_then(|mut value| value.next()).filter(|window| *window != x11rb::NONE))
+    }
+
+    fn read_window(&self, window: u32) -> Result<Window, Box<dyn std::error::Error>> {
+        let wm_class = self.connection
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)?
+            .reply()?;
+
+        let mut title = self.connection
+            .get_property(false, window, self.atoms.net_wm_name, self.atoms.utf8_string, 0, 1024)?
+            .reply()?
+            .value;
+        if title.is_empty() {
+            title = self.connection
+                .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::ANY, 0, 1024)?
+                .reply()?
+                .value;
+        }
+
+        let state = self.connection
+            .get_property(false, window, self.atoms.net_wm_state, AtomEnum::ATOM, 0, 64)?
+            .reply()?;
+        let states = parse_atom_list(state.format, &state.value);
+
+        let mut result = Window {
+            class: parse_wm_class(&wm_class.value),
+            title: String::from_utf8_lossy(&title).to_string(),
+            ..Default::default()
+        };
+        self.atoms.apply_state(&states, &mut result);
+        Ok(result)
+    }
+
+    /// Notifies about the window, the window may already be destroyed in which case it is reported as empty
+    fn report_window(&self, window: Option<u32>) {
+        let window = window
+            .map(|window| self.read_window(window).unwrap_or_else(|e| {
+                log::debug!("Failed to read X11 window {}: {}", window, e);
+                Window::default()
+            }))
+            .unwrap_or_default();
+        (self.on_window_change)(window);
+    }
+
+    fn watch_window(&self, window: u32, event_mask: EventMask) {
+        let attributes = ChangeWindowAttributesAux::new().event_mask(event_mask);
+        let result = self.connection.change_window_attributes(window, &attributes)
+            .map_err(x11rb::errors::ReplyError::from)
+            .and_then(|cookie| cookie.check());
+        if let Err(e) = result {
+            log::debug!("Failed to change X11 window {} attributes: {}", window, e);
+        }
+    }
+
+    fn run_x11(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut active_window = self.active_window()?;
+        if let Some(window) = active_window {
+            self.watch_window(window, EventMask::PROPERTY_CHANGE);
+        }
+        self.report_window(active_window);
+
+        loop {
+            let Event::PropertyNotify(event) = self.connection.wait_for_event()? else {
+                continue;
+            };
+
+            if event.window == self.root && event.atom == self.atoms.net_active_window {
+                let new_active_window = self.active_window()?;
+                if new_active_window == active_window {
+                    continue;
+                }
+                // Title and state changes are only tracked for the active window
+                if let Some(window) = active_window {
+                    self.watch_window(window, EventMask::NO_EVENT);
+                }
+                if let Some(window) = new_active_window {
+                    self.watch_window(window, EventMask::PROPERTY_CHANGE);
+                }
+                active_window = new_active_window;
+                self.report_window(active_window);
+            } else if Some(event.window) == active_window {
+                let watched = [self.atoms.net_wm_name, AtomEnum::WM_NAME.into(), self.atoms.net_wm_state];
+                if watched.contains(&event.atom) {
+                    self.report_window(active_window);
+                }
+            }
+        }
+    }
+}
+
+impl WindowSource for X11Monitor {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn run(&mut self) {
+        if let Err(e) = self.run_x11() {
+            log::error!("X11 window monitor stopped: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atoms() -> X11Atoms {
+        X11Atoms {
+            net_active_window: 1,
+            net_wm_name: 2,
+            net_wm_state: 3,
+            net_wm_state_fullscreen: 4,
+            net_wm_state_maximized_vert: 5,
+            net_wm_state_maximized_horz: 6,
+            net_wm_state_hidden: 7,
+            utf8_string: 8,
+        }
+    }
+
+    fn atom_bytes(atoms: &[Atom]) -> Vec<u8> {
+        atoms.iter().flat_map(|atom| atom.to_ne_bytes()).collect()
+    }
+
+    #[test]
+    fn wm_class_is_the_second_name() {
+        assert_eq!(parse_wm_class(b"navigator\0Firefox\0"), "Firefox");
+        assert_eq!(parse_wm_class(b"kitty\0kitty"), "kitty");
+    }
+
+    #[test]
+    fn wm_class_falls_back_to_the_instance() {
+        assert_eq!(parse_wm_class(b"xterm\0"), "xterm");
+        assert_eq!(parse_wm_class(b""), "");
+    }
+
+    #[test]
+    fn wm_class_with_invalid_utf8() {
+        assert_eq!(parse_wm_class(b"app\0Caf\xe9\0"), "Caf\u{fffd}");
+    }
+
+    #[test]
+    fn atom_list_needs_format_32() {
+        assert_eq!(parse_atom_list(32, &atom_bytes(&[4, 7])), vec![4, 7]);
+        assert_eq!(parse_atom_list(8, &atom_bytes(&[4, 7])), Vec::<Atom>::new());
+        // A truncated trailing atom is ignored
+        assert_eq!(parse_atom_list(32, &[4, 0, 0, 0, 7, 0]).len(), 1);
+    }
+
+    #[test]
+    fn state_from_atoms() {
+        let atoms = atoms();
+        let mut window = Window::default();
+        atoms.apply_state(&parse_atom_list(32, &atom_bytes(&[4, 7])), &mut window);
+        assert!(window.fullscreen && window.minimized && !window.maximized);
+
+        atoms.apply_state(&[5, 6], &mut window);
+        assert!(window.maximized && !window.fullscreen && !window.minimized);
+    }
+
+    #[test]
+    fn maximized_in_one_direction_is_not_maximized() {
+        let mut window = Window::default();
+        atoms().apply_state(&[5], &mut window);
+        assert!(!window.maximized);
+        atoms().apply_state(&[6, 2], &mut window);
+        assert!(!window.maximized);
+    }
+}